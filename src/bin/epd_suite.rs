@@ -0,0 +1,65 @@
+use chess::epd::run_epd_suite;
+use chess::search::SearchLimits;
+use std::{env, process::ExitCode, time::Duration};
+
+const USAGE: &str = "Usage: epd_suite <suite.epd> [--depth N] [--time SECONDS]";
+
+fn parse_args() -> Result<(String, SearchLimits), String> {
+    let mut args = env::args().skip(1);
+    let mut filepath = None;
+    let mut limits = SearchLimits::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => {
+                let depth = args.next().ok_or("--depth needs a number")?;
+                limits.depth = Some(
+                    depth
+                        .parse()
+                        .map_err(|_| format!("Invalid depth: {}", depth))?,
+                );
+            }
+            "--time" => {
+                let seconds = args.next().ok_or("--time needs a number of seconds")?;
+                // Negative, NaN and overflowing times are rejected rather than panicking
+                let time = seconds
+                    .parse()
+                    .ok()
+                    .and_then(|seconds: f64| Duration::try_from_secs_f64(seconds).ok())
+                    .filter(|time| !time.is_zero())
+                    .ok_or_else(|| format!("Invalid time: {}", seconds))?;
+                limits.time = Some(time);
+            }
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ if filepath.is_none() && !arg.starts_with("--") => filepath = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    // Default to a fixed time per position
+    if limits.depth.is_none() && limits.time.is_none() {
+        limits.time = Some(Duration::from_secs(1));
+    }
+    Ok((filepath.ok_or(USAGE)?, limits))
+}
+
+fn main() -> ExitCode {
+    let (filepath, limits) = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run_epd_suite(&filepath, limits, |result| println!("{}", result)) {
+        Ok(summary) => {
+            println!("{}", summary);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Could not run {}: {}", filepath, err);
+            ExitCode::from(2)
+        }
+    }
+}
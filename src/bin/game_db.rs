@@ -0,0 +1,169 @@
+use chess::game::Game;
+use chess::game_database::{GameDatabase, GameFilter, GameRecord};
+use chess::validate::FenValidation;
+use chess::variant::Standard;
+use std::{env, process::ExitCode};
+
+const USAGE: &str = "Usage: game_db <database dir> import <file.pgn[.gz|.zst|.bz2]>...
+       game_db <database dir> search [--player NAME] [--white NAME] [--black NAME]
+               [--event EVENT] [--from DATE] [--to DATE] [--result RESULT] [--fen FEN]";
+
+// Games listed by a search, so huge databases don't flood the terminal
+const MAX_LISTED_GAMES: usize = 50;
+
+enum Command {
+    Import(Vec<String>),
+    Search(GameFilter, Option<String>),
+}
+
+fn parse_args() -> Result<(String, Command), String> {
+    let mut args = env::args().skip(1);
+    let dir = args.next().ok_or(USAGE)?;
+    if dir == "--help" || dir == "-h" {
+        return Err(USAGE.to_string());
+    }
+
+    match args.next().as_deref() {
+        Some("import") => {
+            let files: Vec<String> = args.collect();
+            if files.is_empty() {
+                return Err(format!("import needs at least one PGN file\n{}", USAGE));
+            }
+            Ok((dir, Command::Import(files)))
+        }
+        Some("search") => {
+            let mut filter = GameFilter::default();
+            let mut fen = None;
+            while let Some(arg) = args.next() {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} needs a value", arg))?;
+                match arg.as_str() {
+                    "--player" => filter.player = Some(value),
+                    "--white" => filter.white = Some(value),
+                    "--black" => filter.black = Some(value),
+                    "--event" => filter.event = Some(value),
+                    "--from" => filter.date_from = Some(value),
+                    "--to" => filter.date_to = Some(value),
+                    "--result" => filter.result = Some(value),
+                    "--fen" => fen = Some(value),
+                    _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+                }
+            }
+            Ok((dir, Command::Search(filter, fen)))
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn print_games(games: &[&GameRecord]) {
+    for game in games.iter().take(MAX_LISTED_GAMES) {
+        println!(
+            "#{} {} - {} {} ({}, {})",
+            game.id, game.white, game.black, game.result, game.event, game.date
+        );
+    }
+    if games.len() > MAX_LISTED_GAMES {
+        println!("... and {} more", games.len() - MAX_LISTED_GAMES);
+    }
+    println!("{} games", games.len());
+}
+
+fn search(database: &GameDatabase, filter: &GameFilter, fen: Option<String>) -> Result<(), String> {
+    let fen = match fen {
+        Some(fen) => fen,
+        None => {
+            print_games(&database.filter_games(filter));
+            return Ok(());
+        }
+    };
+
+    let (position, problems) =
+        Game::from_fen_str_validated(&fen, &Standard, FenValidation::Lenient)
+            .map_err(|err| format!("Invalid FEN: {}", err))?;
+    for problem in problems {
+        eprintln!("Warning: {}", problem);
+    }
+    let games = database
+        .games_with_position(&position, filter)
+        .map_err(|err| err.to_string())?;
+    print_games(&games);
+
+    let stats = database
+        .move_stats(&position, filter)
+        .map_err(|err| err.to_string())?;
+    if !stats.is_empty() {
+        println!();
+        println!(
+            "{:<8} {:>6} {:>6} {:>6} {:>6} {:>6} {:>6}",
+            "Move", "Games", "White", "Draw", "Black", "Score", "Elo"
+        );
+    }
+    for stat in stats {
+        let percent = |count: usize| 100. * count as f64 / stat.games as f64;
+        println!(
+            "{:<8} {:>6} {:>5.1}% {:>5.1}% {:>5.1}% {:>5.1}% {:>6}",
+            stat.san,
+            stat.games,
+            percent(stat.white_wins),
+            percent(stat.draws),
+            percent(stat.black_wins),
+            stat.score_percent,
+            stat.average_rating
+                .map_or("-".to_string(), |rating| rating.to_string())
+        );
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let (dir, command) = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    // Only importing creates a database, searching one that isn't there is an error
+    let opened = match command {
+        Command::Import(_) => GameDatabase::open(&dir),
+        Command::Search(..) => GameDatabase::open_existing(&dir),
+    };
+    let mut database = match opened {
+        Ok(database) => database,
+        Err(err) => {
+            eprintln!("Could not open database {}: {}", dir, err);
+            return ExitCode::from(2);
+        }
+    };
+
+    match command {
+        Command::Import(files) => {
+            for file in files {
+                match database.import_pgn(&file) {
+                    Ok(summary) => println!(
+                        "{}: imported {} games ({} positions), skipped {} invalid and {} \
+                         duplicate games",
+                        file,
+                        summary.imported,
+                        summary.positions,
+                        summary.skipped,
+                        summary.duplicates
+                    ),
+                    Err(err) => {
+                        eprintln!("Could not import {}: {}", file, err);
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+        }
+        Command::Search(filter, fen) => {
+            if let Err(message) = search(&database, &filter, fen) {
+                eprintln!("{}", message);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
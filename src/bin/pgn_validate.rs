@@ -0,0 +1,102 @@
+use chess::pgn_validate::validate_file;
+use std::{
+    env,
+    fs::File,
+    io::{BufWriter, Write},
+    process::ExitCode,
+};
+
+const USAGE: &str =
+    "Usage: pgn_validate <file.pgn[.gz|.zst|.bz2]> [--games N] [--clean OUTPUT.pgn] [--verbose]";
+
+struct Args {
+    filepath: String,
+    games_to_read: usize,
+    clean_path: Option<String>,
+    verbose: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args().skip(1);
+    let mut filepath = None;
+    let mut games_to_read = 0;
+    let mut clean_path = None;
+    let mut verbose = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => {
+                let number = args.next().ok_or("--games needs a number")?;
+                games_to_read = number
+                    .parse()
+                    .map_err(|_| format!("Invalid number of games: {}", number))?;
+            }
+            "--clean" => clean_path = Some(args.next().ok_or("--clean needs an output file")?),
+            "--verbose" => verbose = true,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ if filepath.is_none() && !arg.starts_with("--") => filepath = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    Ok(Args {
+        filepath: filepath.ok_or(USAGE)?,
+        games_to_read,
+        clean_path,
+        verbose,
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut clean_output = match &args.clean_path {
+        Some(path) => match File::create(path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("Could not create {}: {}", path, err);
+                return ExitCode::from(2);
+            }
+        },
+        None => None,
+    };
+
+    let summary = validate_file(
+        &args.filepath,
+        args.games_to_read,
+        |report| {
+            // Only the failures by default, every game with --verbose
+            if !report.is_valid() {
+                println!("{}", report);
+            } else if args.verbose {
+                println!("Game {} ok", report.index);
+            }
+        },
+        clean_output.as_mut().map(|output| output as &mut dyn Write),
+    );
+    let flushed = match clean_output.as_mut() {
+        Some(output) => output.flush(),
+        None => Ok(()),
+    };
+
+    match (summary, flushed) {
+        (Ok(summary), Ok(())) => {
+            println!("{}", summary);
+            if summary.valid_games == summary.games {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("Could not validate {}: {}", args.filepath, err);
+            ExitCode::from(2)
+        }
+    }
+}
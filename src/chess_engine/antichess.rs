@@ -0,0 +1,112 @@
+use super::game::{Color, Game, GameState};
+use super::moves::Move;
+use super::piece::PieceType;
+use super::validate::PositionError;
+use super::variant::{STANDARD_PROMOTIONS, Variant};
+
+// Losing chess: captures are compulsory, the king is an ordinary piece without check or
+// castling, and a player wins by losing all their pieces or having no moves
+#[derive(Debug)]
+pub struct Antichess;
+
+impl Variant for Antichess {
+    fn name(&self) -> &'static str {
+        "Antichess"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"
+    }
+
+    fn piece_moves(&self, game: &Game, from_position: (usize, usize)) -> Vec<Move> {
+        game.get_piece_moves(from_position)
+            .into_iter()
+            .filter(|mv| !matches!(mv, Move::Castles(_)))
+            .collect()
+    }
+
+    // Pawns can promote to a king too
+    fn promotions(&self, game: &Game, color: Color, to_row: usize) -> (Vec<PieceType>, bool) {
+        if to_row == game.last_row(color) {
+            let mut promotions = STANDARD_PROMOTIONS.to_vec();
+            promotions.push(PieceType::King);
+            (promotions, false)
+        } else {
+            (Vec::new(), true)
+        }
+    }
+
+    fn is_legal(&self, _after: &mut Game, _mv: Move, _color: Color) -> bool {
+        true
+    }
+
+    fn in_check(&self, _game: &mut Game, _color: Color) -> bool {
+        false
+    }
+
+    fn no_moves_state(&self, color: Color, _in_check: bool) -> GameState {
+        GameState::VariantWin(color)
+    }
+
+    fn restrict_moves(&self, legal_moves: Vec<Move>) -> Vec<Move> {
+        if legal_moves.iter().any(|mv| mv.is_capture()) {
+            legal_moves
+                .into_iter()
+                .filter(|mv| mv.is_capture())
+                .collect()
+        } else {
+            legal_moves
+        }
+    }
+
+    // Kings are ordinary pieces, so a side can have any number of them
+    fn allows_position_error(&self, error: &PositionError) -> bool {
+        matches!(error, PositionError::KingCount(..))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FenValidation;
+
+    fn antichess(fen: &str) -> Game {
+        Game::from_fen_str_validated(fen, &Antichess, FenValidation::Lenient)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn captures_are_compulsory() {
+        let mut game = Game::from_variant(&Antichess);
+        for san in ["e3", "b5"] {
+            game.make_move(game.move_from_san(san).unwrap());
+        }
+        let sans: Vec<String> = game
+            .next_legal_moves
+            .iter()
+            .map(|&mv| game.move_to_san(mv).unwrap())
+            .collect();
+        assert_eq!(sans, ["Bxb5"]);
+    }
+
+    #[test]
+    fn kings_can_be_captured_and_promoted_to() {
+        let game = antichess("8/8/8/8/8/8/1k6/K7 w - - 0 1");
+        assert!(game.move_from_san("Kxb2").is_some());
+        let game = antichess("8/P7/8/8/8/8/8/7k w - - 0 1");
+        assert!(game.move_from_san("a8=K").is_some());
+    }
+
+    #[test]
+    fn losing_every_piece_or_having_no_moves_wins() {
+        let mut game = antichess("8/8/8/8/8/8/1k6/K7 w - - 0 1");
+        game.make_move(game.move_from_san("Kxb2").unwrap());
+        assert_eq!(game.state, GameState::VariantWin(Color::Black));
+
+        // The white pawn is blocked, so White has no moves and wins
+        let mut game = antichess("8/8/8/8/8/p7/8/P6k b - - 0 1");
+        game.make_move(game.move_from_san("a2").unwrap());
+        assert_eq!(game.state, GameState::VariantWin(Color::White));
+    }
+}
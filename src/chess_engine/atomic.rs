@@ -0,0 +1,199 @@
+use super::game::{Color, Game, GameState};
+use super::moves::Move;
+use super::piece::{Piece, PieceType};
+use super::variant::Variant;
+
+// Pieces a capture blew up, so the move can be unmade
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explosion {
+    // Index of the capturing move in the move history
+    pub ply: usize,
+    pub center: (usize, usize),
+    pub destroyed: Vec<((usize, usize), Piece)>,
+}
+
+// Every capture explodes, removing the capturing piece and every piece but pawns around
+// the capture square. Blowing up the opponent's king wins.
+#[derive(Debug)]
+pub struct Atomic;
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "Atomic"
+    }
+
+    // Kings can't capture, since they would blow themselves up
+    fn piece_moves(&self, game: &Game, from_position: (usize, usize)) -> Vec<Move> {
+        game.get_piece_moves(from_position)
+            .into_iter()
+            .filter(|mv| {
+                !matches!(mv, Move::Normal(mv)
+                    if mv.piece.piece_type == PieceType::King && mv.capture.is_some())
+            })
+            .collect()
+    }
+
+    fn is_legal(&self, after: &mut Game, mv: Move, color: Color) -> bool {
+        if !after.has_king(color) {
+            return false;
+        }
+        // Exploding the other king ends the game, even if our own king is left in check
+        if !after.has_king(color.opposite()) {
+            return true;
+        }
+        !after.king_attacked_after(mv, color)
+    }
+
+    // Touching kings can't be attacked, any capture of one would blow up the other
+    fn in_check(&self, game: &mut Game, color: Color) -> bool {
+        !game.kings_touching() && game.in_check(color)
+    }
+
+    fn decisive_state(&self, after: &Game, color: Color, _gives_check: bool) -> Option<GameState> {
+        (!after.has_king(color.opposite())).then_some(GameState::VariantWin(color))
+    }
+
+    fn after_move(&self, game: &mut Game, mv: Move) {
+        game.explode(mv);
+    }
+
+    fn undo_move(&self, game: &mut Game, _mv: Move) {
+        game.unexplode();
+    }
+}
+
+impl Game {
+    pub(crate) fn has_king(&self, color: Color) -> bool {
+        self.board
+            .iter()
+            .flatten()
+            .any(|place| place.is_some_and(|p| p.piece_type == PieceType::King && p.color == color))
+    }
+
+    fn kings_touching(&self) -> bool {
+        let king_squares: Vec<(usize, usize, Color)> = (0..self.rows)
+            .flat_map(|i| (0..self.cols).map(move |j| (i, j)))
+            .filter_map(|(i, j)| match self.board[i][j] {
+                Some(p) if p.piece_type == PieceType::King => Some((i, j, p.color)),
+                _ => None,
+            })
+            .collect();
+        king_squares.iter().any(|&(i, j, color)| {
+            king_squares
+                .iter()
+                .any(|&(k, l, other)| color != other && i.abs_diff(k) <= 1 && j.abs_diff(l) <= 1)
+        })
+    }
+
+    // After a capture has been made, remove the capturing piece and its non-pawn neighbours
+    pub(crate) fn explode(&mut self, mv: Move) {
+        if !mv.is_capture() {
+            return;
+        }
+        let center = mv.get_to_position();
+        let mut destroyed = Vec::new();
+        for i in center.0.saturating_sub(1)..=(center.0 + 1).min(self.rows - 1) {
+            for j in center.1.saturating_sub(1)..=(center.1 + 1).min(self.cols - 1) {
+                let Some(piece) = self.board[i][j] else {
+                    continue;
+                };
+                if (i, j) == center || piece.piece_type != PieceType::Pawn {
+                    destroyed.push(((i, j), piece));
+                    self.board[i][j] = None;
+                }
+            }
+        }
+        self.explosions.push(Explosion {
+            ply: self.move_history.len(),
+            center,
+            destroyed,
+        });
+    }
+
+    // Put back what the move blew up, before the move itself is unmade
+    pub(crate) fn unexplode(&mut self) {
+        if self
+            .explosions
+            .last()
+            .is_some_and(|explosion| explosion.ply == self.move_history.len())
+        {
+            let explosion = self.explosions.pop().unwrap();
+            for ((i, j), piece) in explosion.destroyed {
+                self.board[i][j] = Some(piece);
+            }
+        }
+    }
+
+    // The explosion caused by the last move, if it was a capture in Atomic
+    pub fn last_explosion(&self) -> Option<&Explosion> {
+        self.explosions
+            .last()
+            .filter(|explosion| explosion.ply + 1 == self.move_history.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FenValidation;
+
+    fn atomic(fen: &str) -> Game {
+        Game::from_fen_str_validated(fen, &Atomic, FenValidation::Lenient)
+            .unwrap()
+            .0
+    }
+
+    fn legal_sans(game: &Game) -> Vec<String> {
+        game.next_legal_moves
+            .iter()
+            .map(|&mv| game.move_to_san(mv).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn captures_blow_up_the_capturer_and_pieces_around_but_not_pawns() {
+        let mut game = atomic("4k3/8/2npb3/3p4/8/8/8/3QK3 w - - 0 1");
+        let start = game.clone();
+        game.make_move(game.move_from_san("Qxd5").unwrap());
+
+        let explosion = game.last_explosion().unwrap();
+        assert_eq!(explosion.center, (4, 3));
+        // The queen, knight and bishop; the pawn it took is the move's capture
+        assert_eq!(explosion.destroyed.len(), 3);
+        for (row, col) in [(4, 3), (5, 2), (5, 4)] {
+            assert_eq!(game.board[row][col], None);
+        }
+        // The pawn next to the capture survives
+        assert!(game.board[5][3].is_some_and(|p| p.piece_type == PieceType::Pawn));
+
+        game.undo_move();
+        assert_eq!(game.board, start.board);
+        assert!(game.last_explosion().is_none());
+    }
+
+    #[test]
+    fn blowing_up_the_king_wins() {
+        let mut game = atomic("4k3/4p3/8/8/8/8/8/K3R3 w - - 0 1");
+        game.make_move(game.move_from_san("Rxe7").unwrap());
+        assert!(!game.has_king(Color::Black));
+        assert_eq!(game.state, GameState::VariantWin(Color::White));
+    }
+
+    #[test]
+    fn kings_never_capture_or_blow_themselves_up() {
+        // The pawn checks the king, which can't take it, and taking it with the queen
+        // would blow the king up too
+        let game = atomic("4k3/8/8/8/8/8/3p4/3QK3 w - - 0 1");
+        let sans = legal_sans(&game);
+        assert!(!sans.iter().any(|san| san.contains('x')), "{:?}", sans);
+        assert!(!sans.is_empty());
+    }
+
+    #[test]
+    fn touching_kings_are_never_in_check() {
+        let mut game = atomic("8/8/8/8/8/3k4/3K3r/8 w - - 0 1");
+        assert!(!Atomic.in_check(&mut game, Color::White));
+        let mut game = atomic("8/8/8/8/3k4/8/3K3r/8 w - - 0 1");
+        assert!(Atomic.in_check(&mut game, Color::White));
+    }
+}
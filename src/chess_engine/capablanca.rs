@@ -0,0 +1,83 @@
+use super::game::{Color, Game};
+use super::piece::{PieceDefinition, PieceType, archbishop, chancellor};
+use super::variant::{STANDARD_PROMOTIONS, Variant};
+use std::sync::LazyLock;
+
+// Grand chess plays with the same two pieces
+pub(super) static CAPABLANCA_PIECES: LazyLock<[PieceDefinition; 2]> =
+    LazyLock::new(|| [archbishop(), chancellor()]);
+
+// Standard chess on a 10x8 board, with an archbishop (bishop + knight) and a chancellor
+// (rook + knight) for each side. Castling takes the king to the c or i file.
+#[derive(Debug)]
+pub struct Capablanca;
+
+impl Variant for Capablanca {
+    fn name(&self) -> &'static str {
+        "Capablanca"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1"
+    }
+
+    fn fairy_pieces(&self) -> &[PieceDefinition] {
+        &*CAPABLANCA_PIECES
+    }
+
+    fn promotions(&self, game: &Game, color: Color, to_row: usize) -> (Vec<PieceType>, bool) {
+        if to_row == game.last_row(color) {
+            let mut promotions = STANDARD_PROMOTIONS.to_vec();
+            promotions.extend(CAPABLANCA_PIECES.iter().map(PieceDefinition::piece_type));
+            (promotions, false)
+        } else {
+            (Vec::new(), true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FenValidation;
+
+    fn capablanca(fen: &str) -> Game {
+        Game::from_fen_str_validated(fen, &Capablanca, FenValidation::Strict)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn start_position_round_trips() {
+        let game = Game::from_variant(&Capablanca);
+        assert_eq!(game.to_full_fen_str(), Capablanca.start_fen());
+        // Pawn moves and the jumps of the knights, archbishop and chancellor
+        assert_eq!(game.next_legal_moves.len(), 20 + 4 + 2 + 2);
+    }
+
+    #[test]
+    fn archbishop_and_chancellor_moves() {
+        let game = capablanca("9k/10/10/10/4A5/10/10/K9 w - - 0 1");
+        for san in ["Af6", "Ad2", "Ah7", "Ab1"] {
+            assert!(game.move_from_san(san).is_some(), "{}", san);
+        }
+        assert_eq!(game.move_from_san("Ae8"), None);
+
+        let game = capablanca("9k/10/10/10/4C5/10/10/K9 w - - 0 1");
+        for san in ["Cf6", "Ce8", "Cj4", "Ca4"] {
+            assert!(game.move_from_san(san).is_some(), "{}", san);
+        }
+        assert_eq!(game.move_from_san("Cf5"), None);
+    }
+
+    #[test]
+    fn pawns_promote_to_fairy_pieces_too() {
+        let mut game = capablanca("k9/4P5/10/10/10/10/10/9K w - - 0 1");
+        let mv = game
+            .move_from_san("e8=C")
+            .expect("Chancellor promotion is legal");
+        assert_eq!(game.move_to_san(mv).as_deref(), Some("e8=C+"));
+        game.make_move(mv);
+        assert_eq!(game.to_fen_str(), "k3C5/10/10/10/10/10/10/9K");
+    }
+}
@@ -0,0 +1,263 @@
+use super::game::{Color, Game, GameState};
+use super::moves::{DropMove, Move};
+use super::piece::{Piece, PieceType};
+use super::variant::Variant;
+
+// Pieces that can be held in a pocket, in the order they are shown and written in FENs
+pub const POCKET_PIECES: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+// Captured pieces a player can drop back onto the board
+#[derive(Copy, Clone, Debug, Default, PartialEq, Hash, Eq)]
+pub struct Pocket {
+    counts: [u8; 5],
+}
+
+impl Pocket {
+    pub fn count(&self, piece_type: PieceType) -> u8 {
+        self.counts[pocket_index(piece_type)]
+    }
+
+    pub fn add(&mut self, piece_type: PieceType) {
+        self.counts[pocket_index(piece_type)] += 1;
+    }
+
+    pub fn remove(&mut self, piece_type: PieceType) {
+        self.counts[pocket_index(piece_type)] -= 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&count| count == 0)
+    }
+
+    // Piece types in the pocket with how many of each, leaving out the ones there are none of
+    pub fn pieces(&self) -> impl Iterator<Item = (PieceType, u8)> + '_ {
+        POCKET_PIECES
+            .iter()
+            .map(|&piece_type| (piece_type, self.count(piece_type)))
+            .filter(|&(_, count)| count > 0)
+    }
+}
+
+fn pocket_index(piece_type: PieceType) -> usize {
+    POCKET_PIECES
+        .iter()
+        .position(|&pocket_piece| pocket_piece == piece_type)
+        .expect("Kings can't go in a pocket")
+}
+
+// Standard chess, except captured pieces join the capturer's pocket and can be dropped
+// back onto an empty square instead of moving
+#[derive(Debug)]
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+
+    fn has_pockets(&self) -> bool {
+        true
+    }
+
+    fn after_move(&self, game: &mut Game, mv: Move) {
+        game.update_pockets(mv, false);
+    }
+
+    fn undo_move(&self, game: &mut Game, mv: Move) {
+        game.update_pockets(mv, true);
+    }
+
+    fn extra_moves(&self, game: &Game, color: Color) -> Vec<Move> {
+        game.drop_moves(color)
+    }
+}
+
+impl Game {
+    pub fn pocket(&self, color: Color) -> &Pocket {
+        match color {
+            Color::White => &self.pockets.0,
+            Color::Black => &self.pockets.1,
+        }
+    }
+
+    pub fn pocket_mut(&mut self, color: Color) -> &mut Pocket {
+        match color {
+            Color::White => &mut self.pockets.0,
+            Color::Black => &mut self.pockets.1,
+        }
+    }
+
+    // Every square a piece in the pocket of `color` could be dropped on. Pawns can't go on
+    // the first or last rank.
+    pub fn drop_moves(&self, color: Color) -> Vec<Move> {
+        let mut drops = Vec::new();
+        let pawn_rank = self.pawn_row(color);
+        for (piece_type, _) in self.pocket(color).pieces() {
+            for i in 0..self.rows {
+                if piece_type == PieceType::Pawn && (i == 0 || i == self.rows - 1) {
+                    continue;
+                }
+                for j in 0..self.cols {
+                    if self.board[i][j].is_some() {
+                        continue;
+                    }
+                    // A pawn dropped on its starting rank can still move two squares
+                    drops.push(Move::Drop(DropMove {
+                        piece: Piece {
+                            color,
+                            piece_type,
+                            has_moved: !(piece_type == PieceType::Pawn && i == pawn_rank),
+                            promoted: false,
+                        },
+                        to_position: (i, j),
+                        game_state: GameState::Normal,
+                    }));
+                }
+            }
+        }
+        drops
+    }
+
+    // The piece a move takes, while the move is on the board
+    pub(crate) fn captured_piece(&self, mv: Move) -> Option<Piece> {
+        match mv {
+            Move::Normal(mv) => mv.capture,
+            Move::Promotion(mv) => mv.capture,
+            // The pawn taken en passant is the opponent's of the one that took it
+            Move::EnPassant(mv) => {
+                let pawn = self.board[mv.to_position.0][mv.to_position.1]?;
+                Some(Piece {
+                    color: pawn.color.opposite(),
+                    ..pawn
+                })
+            }
+            Move::Castles(_) | Move::Drop(_) => None,
+        }
+    }
+
+    // Put a captured piece in the capturer's pocket, or take it back out when unmaking
+    pub(crate) fn update_pockets(&mut self, mv: Move, unmake: bool) {
+        let Some(captured) = self.captured_piece(mv) else {
+            return;
+        };
+        let piece_type = if captured.promoted {
+            PieceType::Pawn
+        } else {
+            captured.piece_type
+        };
+        let pocket = self.pocket_mut(captured.color.opposite());
+        if unmake {
+            pocket.remove(piece_type);
+        } else {
+            pocket.add(piece_type);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FenValidation;
+
+    fn crazyhouse(fen: &str) -> Game {
+        Game::from_fen_str_validated(fen, &Crazyhouse, FenValidation::Strict)
+            .unwrap()
+            .0
+    }
+
+    fn play(game: &mut Game, sans: &str) {
+        for san in sans.split_whitespace() {
+            let mv = game.move_from_san(san).expect("Test move is legal");
+            game.make_move(mv);
+        }
+    }
+
+    #[test]
+    fn captures_go_to_the_capturers_pocket_and_can_be_dropped() {
+        let mut game = Game::from_variant(&Crazyhouse);
+        play(&mut game, "e4 d5 exd5 Qxd5");
+        assert_eq!(game.pocket(Color::White).count(PieceType::Pawn), 1);
+        assert_eq!(game.pocket(Color::Black).count(PieceType::Pawn), 1);
+
+        play(&mut game, "P@e6");
+        assert_eq!(game.pocket(Color::White).count(PieceType::Pawn), 0);
+        assert_eq!(
+            game.board[5][4].map(|p| (p.piece_type, p.color)),
+            Some((PieceType::Pawn, Color::White))
+        );
+        assert!(game.to_full_fen_str().contains("[p]"));
+
+        game.undo_move();
+        assert_eq!(game.pocket(Color::White).count(PieceType::Pawn), 1);
+        assert_eq!(game.board[5][4], None);
+    }
+
+    #[test]
+    fn pawns_taken_en_passant_go_to_the_pocket() {
+        let mut game = crazyhouse("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        play(&mut game, "exd6");
+        assert_eq!(game.pocket(Color::White).count(PieceType::Pawn), 1);
+        game.undo_move();
+        assert!(game.pocket(Color::White).is_empty());
+    }
+
+    #[test]
+    fn pawns_are_not_dropped_on_the_first_or_last_rank() {
+        let game = crazyhouse("4k3/8/8/8/8/8/8/4K3[PN] w - - 0 1");
+        let drops: Vec<(PieceType, (usize, usize))> = game
+            .next_legal_moves
+            .iter()
+            .filter_map(|mv| match mv {
+                Move::Drop(drop) => Some((drop.piece.piece_type, drop.to_position)),
+                _ => None,
+            })
+            .collect();
+        // 62 empty squares for the knight, the 48 of them off the back ranks for the pawn
+        assert_eq!(drops.len(), 62 + 48);
+        assert!(
+            drops
+                .iter()
+                .all(|&(piece_type, (row, _))| piece_type != PieceType::Pawn
+                    || (row != 0 && row != 7))
+        );
+    }
+
+    #[test]
+    fn promoted_pieces_go_back_to_the_pocket_as_pawns() {
+        let mut game = crazyhouse("1n2k3/P7/8/4b3/8/8/8/4K3 w - - 0 1");
+        play(&mut game, "axb8=Q+");
+        assert!(game.board[7][1].is_some_and(|p| p.promoted));
+        assert_eq!(game.pocket(Color::White).count(PieceType::Knight), 1);
+        assert!(game.to_full_fen_str().starts_with("1Q~2k3/"));
+
+        play(&mut game, "Bxb8");
+        assert_eq!(game.pocket(Color::Black).count(PieceType::Pawn), 1);
+        assert_eq!(game.pocket(Color::Black).count(PieceType::Queen), 0);
+    }
+
+    #[test]
+    fn a_drop_can_block_a_check_or_mate() {
+        // Black's only way out of the rook check is dropping the knight in between
+        let game = crazyhouse("R5k1/5ppp/8/8/8/8/8/6K1[n] b - - 0 1");
+        let sans: Vec<String> = game
+            .next_legal_moves
+            .iter()
+            .map(|&mv| game.move_to_san(mv).unwrap())
+            .collect();
+        assert_eq!(sans.len(), 5);
+        assert!(
+            sans.iter()
+                .all(|san| san.starts_with("N@") && san.ends_with('8'))
+        );
+
+        let mut game = crazyhouse("6k1/5ppp/8/8/8/8/8/6K1[R] w - - 0 1");
+        play(&mut game, "R@a8#");
+        assert_eq!(game.state, GameState::Checkmate(Color::Black));
+    }
+}
@@ -0,0 +1,99 @@
+use super::game::Game;
+use super::moves::Move;
+use super::variant::is_standard;
+use std::{collections::HashMap, io::Error, sync::OnceLock};
+
+// Bundled so classification works wherever the binary is run from
+const ECO_TABLE: &str = include_str!("../../assets/eco.tsv");
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EcoOpening {
+    pub eco: String,
+    pub name: String,
+}
+
+// ECO openings keyed by the hash of the position they reach
+pub struct EcoClassifier {
+    openings: HashMap<u64, EcoOpening>,
+}
+
+impl EcoClassifier {
+    // Table lines are "ECO<tab>Name<tab>moves in SAN", '#' starts a comment line
+    pub fn from_table(table: &str) -> Result<EcoClassifier, Error> {
+        let mut openings = HashMap::new();
+        for (i, line) in table.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 3 {
+                return Err(Error::other(format!(
+                    "line {}: expected ECO code, name and moves",
+                    i + 1
+                )));
+            }
+
+            let mut position = Game::from_standard_board();
+            for san in fields[2].split_whitespace() {
+                // Skip move numbers
+                if san.ends_with('.') {
+                    continue;
+                }
+                let mv = position
+                    .move_from_san(san)
+                    .ok_or_else(|| Error::other(format!("line {}: illegal move {}", i + 1, san)))?;
+                position.make_search_move(mv);
+            }
+            openings.insert(
+                position.position_hash(),
+                EcoOpening {
+                    eco: fields[0].to_string(),
+                    name: fields[1].to_string(),
+                },
+            );
+        }
+        Ok(EcoClassifier { openings })
+    }
+
+    pub fn bundled() -> &'static EcoClassifier {
+        static CLASSIFIER: OnceLock<EcoClassifier> = OnceLock::new();
+        CLASSIFIER.get_or_init(|| {
+            EcoClassifier::from_table(ECO_TABLE).expect("Bundled ECO table is invalid")
+        })
+    }
+
+    pub fn lookup(&self, position: &Game) -> Option<&EcoOpening> {
+        self.openings.get(&position.position_hash())
+    }
+
+    // Opening of the deepest position in the game that is in the table
+    pub fn classify(&self, game: &Game) -> Option<&EcoOpening> {
+        let mut position = game.clone();
+        while position.undo_move().is_some() {}
+        self.classify_from(position, &game.move_history)
+    }
+
+    // Same as classify, for moves played from the standard starting position
+    pub fn classify_moves(&self, moves: &[Move]) -> Option<&EcoOpening> {
+        self.classify_from(Game::from_standard_board(), moves)
+    }
+
+    fn classify_from(&self, mut position: Game, moves: &[Move]) -> Option<&EcoOpening> {
+        let mut opening = self.lookup(&position);
+        for &mv in moves {
+            position.make_search_move(mv);
+            opening = self.lookup(&position).or(opening);
+        }
+        opening
+    }
+}
+
+impl Game {
+    pub fn eco_opening(&self) -> Option<&'static EcoOpening> {
+        // The table only has standard chess openings
+        if !is_standard(self.variant) {
+            return None;
+        }
+        EcoClassifier::bundled().classify(self)
+    }
+}
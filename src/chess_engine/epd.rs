@@ -0,0 +1,355 @@
+use super::game::Game;
+use super::moves::Move;
+use super::search::{MATE_SCORE, SearchLimits, SearchResult, is_mate_score};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Error},
+};
+
+// Extended Position Description: the first four FEN fields followed by opcode operations
+#[derive(Clone, Debug)]
+pub struct Epd {
+    pub fen: String,
+    pub position: Game,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    pub fn parse(line: &str) -> Result<Epd, Error> {
+        let line = line.trim();
+        let mut fields = Vec::new();
+        let mut rest = line;
+        for _ in 0..4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        if fields.iter().any(|field| field.is_empty()) {
+            return Err(Error::other(format!(
+                "EPD needs board, side to move, castling and en passant fields: {}",
+                line
+            )));
+        }
+
+        let fen = fields.join(" ");
+        Ok(Epd {
+            position: Game::from_fen_str(&fen)?,
+            fen,
+            operations: parse_operations(rest)?,
+        })
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id")
+            .and_then(|operands| operands.first())
+            .map(|id| id.as_str())
+    }
+
+    // c0 to c9 comment operations
+    pub fn comment(&self, number: u8) -> Option<&str> {
+        self.operation(&format!("c{}", number))
+            .and_then(|operands| operands.first())
+            .map(|comment| comment.as_str())
+    }
+
+    pub fn analysis_depth(&self) -> Option<u32> {
+        self.operation("acd")?.first()?.parse().ok()
+    }
+
+    pub fn centipawn_evaluation(&self) -> Option<i32> {
+        self.operation("ce")?.first()?.parse().ok()
+    }
+
+    pub fn best_moves(&self) -> Result<Vec<Move>, Error> {
+        self.san_operands_to_moves("bm")
+    }
+
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, Error> {
+        self.san_operands_to_moves("am")
+    }
+
+    // Principal variation, played out from the position
+    pub fn predicted_variation(&self) -> Result<Vec<Move>, Error> {
+        let mut position = self.position.clone();
+        let mut moves = Vec::new();
+        for san in self.operation("pv").unwrap_or(&[]) {
+            let mv = position.move_from_san(san).ok_or_else(|| {
+                Error::other(format!("pv move {} is not legal in its position", san))
+            })?;
+            position.make_move(mv);
+            moves.push(mv);
+        }
+        Ok(moves)
+    }
+
+    fn san_operands_to_moves(&self, opcode: &str) -> Result<Vec<Move>, Error> {
+        self.operation(opcode)
+            .unwrap_or(&[])
+            .iter()
+            .map(|san| {
+                self.position.move_from_san(san).ok_or_else(|| {
+                    Error::other(format!(
+                        "{} move {} is not legal in {}",
+                        opcode, san, self.fen
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(name, _)| name == opcode) {
+            Some(operation) => operation.1 = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    pub fn to_epd_str(&self) -> String {
+        let mut result = self.fen.clone();
+        for (opcode, operands) in &self.operations {
+            result.push(' ');
+            result.push_str(opcode);
+            for operand in operands {
+                result.push(' ');
+                if operand.is_empty() || operand.contains([' ', ';', '"']) {
+                    result.push_str(&format!("\"{}\"", operand.replace('"', "'")));
+                } else {
+                    result.push_str(operand);
+                }
+            }
+            result.push(';');
+        }
+        result
+    }
+}
+
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, Error> {
+    let mut operations = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(chr) = chars.next() {
+        match chr {
+            ';' => {
+                if !tokens.is_empty() {
+                    let opcode = tokens.remove(0);
+                    operations.push((opcode, std::mem::take(&mut tokens)));
+                }
+            }
+            '"' => {
+                let mut operand = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(chr) => operand.push(chr),
+                        None => return Err(Error::other("Unterminated string in EPD operation")),
+                    }
+                }
+                tokens.push(operand);
+            }
+            chr if chr.is_whitespace() => {}
+            chr => {
+                let mut token = chr.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == ';' || next == '"' {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    if !tokens.is_empty() {
+        return Err(Error::other(format!(
+            "EPD operation {} is missing its ';'",
+            tokens[0]
+        )));
+    }
+    Ok(operations)
+}
+
+#[derive(Clone, Debug)]
+pub struct EpdTestResult {
+    pub id: String,
+    pub solved: bool,
+    pub found: String,
+    pub expected: String,
+    pub search: SearchResult,
+}
+
+impl fmt::Display for EpdTestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let score = if is_mate_score(self.search.score) {
+            let moves = (MATE_SCORE - self.search.score.abs() + 1) / 2;
+            format!("mate {}", moves * self.search.score.signum())
+        } else {
+            format!("{} cp", self.search.score)
+        };
+        write!(
+            f,
+            "{}: {} (found {}, expected {}; {}, depth {}, {} nodes)",
+            self.id,
+            if self.solved { "solved" } else { "FAILED" },
+            self.found,
+            self.expected,
+            score,
+            self.search.depth,
+            self.search.nodes
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EpdSuiteSummary {
+    pub positions: usize,
+    pub solved: usize,
+    pub skipped: usize,
+}
+
+impl fmt::Display for EpdSuiteSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Solved {}/{} positions ({} skipped without bm or am)",
+            self.solved, self.positions, self.skipped
+        )
+    }
+}
+
+// Search a bm/am test position. Returns None for positions without either opcode.
+pub fn run_epd_test(
+    epd: &Epd,
+    index: usize,
+    limits: SearchLimits,
+) -> Result<Option<EpdTestResult>, Error> {
+    let best_moves = epd.best_moves()?;
+    let avoid_moves = epd.avoid_moves()?;
+    if best_moves.is_empty() && avoid_moves.is_empty() {
+        return Ok(None);
+    }
+
+    let search = epd.position.search(limits);
+    let solved = match search.best_move {
+        Some(mv) => {
+            (best_moves.is_empty() || best_moves.contains(&mv)) && !avoid_moves.contains(&mv)
+        }
+        None => false,
+    };
+
+    let mut expected = Vec::new();
+    if let Some(operands) = epd.operation("bm") {
+        expected.push(format!("bm {}", operands.join(" ")));
+    }
+    if let Some(operands) = epd.operation("am") {
+        expected.push(format!("am {}", operands.join(" ")));
+    }
+
+    Ok(Some(EpdTestResult {
+        id: epd
+            .id()
+            .map_or_else(|| format!("#{}", index), |id| id.to_string()),
+        solved,
+        found: search
+            .best_move
+            .and_then(|mv| epd.position.move_to_san(mv))
+            .unwrap_or_else(|| "(none)".to_string()),
+        expected: expected.join(", "),
+        search,
+    }))
+}
+
+pub fn run_epd_suite(
+    filepath: &str,
+    limits: SearchLimits,
+    mut on_result: impl FnMut(&EpdTestResult),
+) -> Result<EpdSuiteSummary, Error> {
+    let reader = BufReader::new(File::open(filepath)?);
+    let mut summary = EpdSuiteSummary::default();
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let epd =
+            Epd::parse(&line).map_err(|err| Error::other(format!("line {}: {}", i + 1, err)))?;
+
+        match run_epd_test(&epd, i + 1, limits)? {
+            Some(result) => {
+                summary.positions += 1;
+                if result.solved {
+                    summary.solved += 1;
+                }
+                on_result(&result);
+            }
+            None => summary.skipped += 1,
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_position_and_operations() {
+        let epd = Epd::parse(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4; \
+             id \"open game\"; c0 \"two, moves\"; acd 12; ce 35;",
+        )
+        .unwrap();
+        assert_eq!(
+            epd.fen,
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -"
+        );
+        assert_eq!(epd.id(), Some("open game"));
+        assert_eq!(epd.comment(0), Some("two, moves"));
+        assert_eq!(epd.analysis_depth(), Some(12));
+        assert_eq!(epd.centipawn_evaluation(), Some(35));
+        assert_eq!(epd.best_moves().unwrap().len(), 2);
+        assert!(epd.avoid_moves().unwrap().is_empty());
+    }
+
+    #[test]
+    fn en_passant_square_allows_the_capture() {
+        let epd = Epd::parse("4k3/8/8/3pP3/8/8/8/4K3 w - d6 bm exd6;").unwrap();
+        let best_moves = epd.best_moves().unwrap();
+        assert_eq!(best_moves.len(), 1);
+        assert!(best_moves[0].is_capture());
+
+        // Without the square the capture isn't a legal move
+        let epd = Epd::parse("4k3/8/8/3pP3/8/8/8/4K3 w - - bm exd6;").unwrap();
+        assert!(epd.best_moves().is_err());
+    }
+
+    #[test]
+    fn rejects_missing_fields_and_unterminated_operations() {
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w -").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"x\"").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"x;").is_err());
+    }
+
+    #[test]
+    fn writes_operations_back_quoting_where_needed() {
+        let line = "4k3/8/8/8/8/8/8/4K3 w - - id \"a b\"; acd 3;";
+        let mut epd = Epd::parse(line).unwrap();
+        assert_eq!(epd.to_epd_str(), line);
+        epd.set_operation("acd", vec!["4".to_string()]);
+        assert_eq!(
+            epd.to_epd_str(),
+            "4k3/8/8/8/8/8/8/4K3 w - - id \"a b\"; acd 4;"
+        );
+    }
+}
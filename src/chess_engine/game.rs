@@ -1,17 +1,41 @@
 use core::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use super::atomic::Explosion;
+use super::crazyhouse::Pocket;
 use super::moves::*;
-use super::piece::{Piece, PieceType};
+use super::piece::{Movement, Piece, PieceDefinition, PieceType};
+use super::variant::{Standard, Variant};
 
-pub type BoardType = [[Option<Piece>; 8]; 8];
+// Largest number of ranks or files a board can have, as in 10x10 Grand chess
+pub const MAX_BOARD_SIZE: usize = 10;
+
+// Indexed by row then column. Only the first `rows` x `cols` squares are used.
+pub type BoardType = [[Option<Piece>; MAX_BOARD_SIZE]; MAX_BOARD_SIZE];
 
 #[derive(Clone, Debug)]
 pub struct Game {
     pub board: BoardType,
+    pub rows: usize,
+    pub cols: usize,
     pub next_player: Color,
     pub move_history: Vec<Move>,
+    pub undone_moves: Vec<Move>,
     pub state: GameState,
     pub next_legal_moves: Vec<Move>,
+    // Fischer random castling rules: the king and rooks can start on any file
+    pub chess960: bool,
+    pub variant: &'static dyn Variant,
+    // Checks White and Black had given before the starting position, from a three-check FEN
+    pub start_checks: (u32, u32),
+    // En passant square of the starting position, from its FEN, until the first move is made
+    pub start_en_passant: Option<(usize, usize)>,
+    // Halfmove clock and fullmove number of the starting position, from its FEN
+    pub start_move_counters: (usize, usize),
+    // Pieces White and Black have captured and can drop, in Crazyhouse
+    pub pockets: (Pocket, Pocket),
+    // What each capture blew up in Atomic, so moves can be taken back
+    pub explosions: Vec<Explosion>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
@@ -20,6 +44,22 @@ pub enum GameState {
     InCheck(Color),
     Checkmate(Color),
     Stalemate,
+    // Won by a variant's own goal, like reaching the hill, with the winner
+    VariantWin(Color),
+    // Drawn by a variant's own rule, like both kings reaching the goal in Racing Kings
+    VariantDraw,
+}
+
+impl GameState {
+    pub fn is_over(&self) -> bool {
+        matches!(
+            self,
+            GameState::Checkmate(_)
+                | GameState::Stalemate
+                | GameState::VariantWin(_)
+                | GameState::VariantDraw
+        )
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
@@ -39,41 +79,100 @@ impl fmt::Display for Color {
 }
 
 pub fn board_position_to_notation(row: usize, col: usize) -> String {
-    if row > 8 || col > 8 {
-        panic!("Row: {}, Height: {}. Cannot be greater than 8!", row, col);
+    if row >= MAX_BOARD_SIZE || col >= MAX_BOARD_SIZE {
+        panic!(
+            "Row: {}, Height: {}. Cannot be greater than {}!",
+            row, col, MAX_BOARD_SIZE
+        );
     }
     let firstchar: char = ('a' as u8 + col as u8) as char;
-    let secondchar: char = ('1' as u8 + row as u8) as char;
-    return format!("{}{}", firstchar, secondchar);
+    return format!("{}{}", firstchar, row + 1);
 }
 
-pub fn valid_position(position: (usize, usize)) -> bool {
-    if position.0 >= 8 || position.1 >= 8 {
-        return false;
-    }
-    return true;
+// Not meant to be unpredictable, just different from game to game
+pub fn random_chess960_index() -> usize {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos());
+    nanos as usize % 960
 }
 
 impl Game {
     pub fn from_blank_board() -> Game {
-        let blank_board: [[Option<Piece>; 8]; 8] = [[None; 8]; 8];
+        Self::from_blank_board_sized(8, 8)
+    }
+
+    pub fn from_blank_board_sized(rows: usize, cols: usize) -> Game {
+        assert!(rows <= MAX_BOARD_SIZE && cols <= MAX_BOARD_SIZE);
+        let blank_board: BoardType = [[None; MAX_BOARD_SIZE]; MAX_BOARD_SIZE];
 
         let mut new_game = Game {
             board: blank_board,
+            rows,
+            cols,
             next_player: Color::White,
             move_history: Vec::new(),
+            undone_moves: Vec::new(),
             state: GameState::Normal,
             next_legal_moves: Vec::new(),
+            chess960: false,
+            variant: &Standard,
+            start_checks: (0, 0),
+            start_en_passant: None,
+            start_move_counters: (0, 1),
+            pockets: (Pocket::default(), Pocket::default()),
+            explosions: Vec::new(),
         };
         new_game.next_legal_moves = new_game.get_all_legal_moves(true);
         return new_game;
     }
 
     pub fn clear(&mut self) {
-        for i in 0..8 {
-            for j in 0..8 {
-                self.board[i][j] = None;
-            }
+        self.board = [[None; MAX_BOARD_SIZE]; MAX_BOARD_SIZE];
+    }
+
+    pub fn valid_position(&self, position: (usize, usize)) -> bool {
+        position.0 < self.rows && position.1 < self.cols
+    }
+
+    // Row of the back rank, where the king and rooks castle from
+    pub fn home_row(&self, color: Color) -> usize {
+        match color {
+            Color::White => 0,
+            Color::Black => self.rows - 1,
+        }
+    }
+
+    // Row pawns start on and can move two squares from. Pawns behind it, like Horde's
+    // first-rank pawns, can too.
+    pub fn pawn_row(&self, color: Color) -> usize {
+        let row = self.variant.pawn_rows();
+        match color {
+            Color::White => row,
+            Color::Black => self.rows - 1 - row,
+        }
+    }
+
+    // Row pawns have to promote on
+    pub fn last_row(&self, color: Color) -> usize {
+        self.home_row(color.opposite())
+    }
+
+    // The variant's definition of the fairy piece written with `letter`
+    pub fn fairy_piece(&self, letter: char) -> Option<&'static PieceDefinition> {
+        self.variant
+            .fairy_pieces()
+            .iter()
+            .find(|definition| definition.letter == letter)
+    }
+
+    // How a piece of this type moves in the game's variant
+    pub fn movements(&self, piece_type: PieceType) -> &'static [Movement] {
+        match piece_type {
+            PieceType::Fairy(letter) => self
+                .fairy_piece(letter)
+                .map_or(&[], |definition| &definition.movements),
+            _ => piece_type.movements(),
         }
     }
 
@@ -88,6 +187,7 @@ impl Game {
                 color: Color::White,
                 piece_type: PieceType::Pawn,
                 has_moved: false,
+                promoted: false,
             };
             new_game.board[1][i] = Some(white_pawn);
         }
@@ -97,11 +197,13 @@ impl Game {
             color: Color::White,
             piece_type: PieceType::Rook,
             has_moved: false,
+            promoted: false,
         });
         new_game.board[0][7] = Some(Piece {
             color: Color::White,
             piece_type: PieceType::Rook,
             has_moved: false,
+            promoted: false,
         });
 
         // Knights
@@ -109,11 +211,13 @@ impl Game {
             color: Color::White,
             piece_type: PieceType::Knight,
             has_moved: false,
+            promoted: false,
         });
         new_game.board[0][6] = Some(Piece {
             color: Color::White,
             piece_type: PieceType::Knight,
             has_moved: false,
+            promoted: false,
         });
 
         // Bishops
@@ -121,11 +225,13 @@ impl Game {
             color: Color::White,
             piece_type: PieceType::Bishop,
             has_moved: false,
+            promoted: false,
         });
         new_game.board[0][5] = Some(Piece {
             color: Color::White,
             piece_type: PieceType::Bishop,
             has_moved: false,
+            promoted: false,
         });
 
         // Queen
@@ -133,6 +239,7 @@ impl Game {
             color: Color::White,
             piece_type: PieceType::Queen,
             has_moved: false,
+            promoted: false,
         });
 
         // King
@@ -140,6 +247,7 @@ impl Game {
             color: Color::White,
             piece_type: PieceType::King,
             has_moved: false,
+            promoted: false,
         });
 
         // Mirror board
@@ -159,9 +267,88 @@ impl Game {
         return new_game;
     }
 
+    // Start position number 0 to 959 in the standard numbering, where 518 is the classical setup
+    pub fn from_chess960_index(index: usize) -> Option<Game> {
+        if index >= 960 {
+            return None;
+        }
+        let mut back_rank: [Option<PieceType>; 8] = [None; 8];
+        let mut n = index;
+
+        // Bishops on opposite colours
+        back_rank[(n % 4) * 2 + 1] = Some(PieceType::Bishop);
+        n /= 4;
+        back_rank[(n % 4) * 2] = Some(PieceType::Bishop);
+        n /= 4;
+
+        // Then the queen and knights on the remaining empty squares
+        let place_on_empty =
+            |back_rank: &mut [Option<PieceType>; 8], nth: usize, piece_type: PieceType| {
+                let col = (0..8)
+                    .filter(|&col| back_rank[col].is_none())
+                    .nth(nth)
+                    .unwrap();
+                back_rank[col] = Some(piece_type);
+            };
+        place_on_empty(&mut back_rank, n % 6, PieceType::Queen);
+        n /= 6;
+        let knights = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ][n];
+        // The second knight goes first so the first one's index isn't shifted
+        place_on_empty(&mut back_rank, knights.1, PieceType::Knight);
+        place_on_empty(&mut back_rank, knights.0, PieceType::Knight);
+
+        // King between the rooks on the last three squares
+        for piece_type in [PieceType::Rook, PieceType::King, PieceType::Rook] {
+            place_on_empty(&mut back_rank, 0, piece_type);
+        }
+
+        let mut new_game = Self::from_blank_board();
+        new_game.chess960 = true;
+        for (col, piece_type) in back_rank.iter().enumerate() {
+            for (row, pawn_row, color) in [(0, 1, Color::White), (7, 6, Color::Black)] {
+                new_game.board[row][col] = Some(Piece {
+                    color,
+                    piece_type: piece_type.unwrap(),
+                    has_moved: false,
+                    promoted: false,
+                });
+                new_game.board[pawn_row][col] = Some(Piece {
+                    color,
+                    piece_type: PieceType::Pawn,
+                    has_moved: false,
+                    promoted: false,
+                });
+            }
+        }
+        new_game.next_legal_moves = new_game.get_all_legal_moves(true);
+        Some(new_game)
+    }
+
+    pub fn from_variant(variant: &'static dyn Variant) -> Game {
+        Self::read_fen(variant.start_fen(), variant)
+            .expect("Variant start position is not valid FEN")
+            .0
+    }
+
+    pub fn set_variant(&mut self, variant: &'static dyn Variant) {
+        self.variant = variant;
+        self.next_legal_moves = self.get_all_legal_moves(true);
+    }
+
     pub fn print_board(&self) {
-        for row in &self.board {
-            println!("{:?}", row);
+        for row in &self.board[..self.rows] {
+            println!("{:?}", &row[..self.cols]);
         }
     }
 }
@@ -0,0 +1,818 @@
+use super::game::{Color, Game};
+use super::moves::Move;
+use super::pgn_move_conversion::piece_to_san_char;
+use super::pgn_parse::{PgnGame, SEVEN_TAG_ROSTER};
+use super::pgn_read::{PgnReader, RawPgnGame};
+use rayon::prelude::*;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+const GAMES_FILE: &str = "games.pgn";
+const GAME_INDEX_FILE: &str = "games.tsv";
+const POSITION_INDEX_FILE: &str = "positions.bin";
+
+// hash (8 bytes), game id (4), ply (2), move played from the position (4)
+const POSITION_RECORD_SIZE: u64 = 18;
+// Stored as the move of the last position of a game
+const NO_MOVE: u32 = u32::MAX;
+
+const IMPORT_BATCH_SIZE: usize = 1024;
+// Positions sorted in memory before they are written out as a run to merge, about 24 MB
+const POSITION_RUN_SIZE: usize = 1 << 20;
+
+// Hash of each position in a game with the move played from it
+type GamePositions = Vec<(u64, u32)>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+    pub id: u32,
+    pub offset: u64,
+    pub length: u64,
+    pub white: String,
+    pub black: String,
+    pub event: String,
+    pub date: String,
+    pub result: String,
+    pub white_elo: Option<u32>,
+    pub black_elo: Option<u32>,
+    // Of the tags and moves, to recognise a game that is imported again
+    pub fingerprint: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameFilter {
+    // Matches either colour
+    pub player: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub event: Option<String>,
+    // Inclusive, in PGN date format (YYYY.MM.DD, trailing parts may be left out)
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub result: Option<String>,
+}
+
+impl GameFilter {
+    pub fn matches(&self, record: &GameRecord) -> bool {
+        let contains = |field: &str, pattern: &Option<String>| match pattern {
+            Some(pattern) => field.to_lowercase().contains(&pattern.to_lowercase()),
+            None => true,
+        };
+        let date = record.date.replace('?', "0");
+
+        (self.player.is_none()
+            || contains(&record.white, &self.player)
+            || contains(&record.black, &self.player))
+            && contains(&record.white, &self.white)
+            && contains(&record.black, &self.black)
+            && contains(&record.event, &self.event)
+            && self.date_from.as_ref().is_none_or(|from| date >= *from)
+            && self
+                .date_to
+                .as_ref()
+                .is_none_or(|to| date <= *to || date.starts_with(to.as_str()))
+            && self
+                .result
+                .as_ref()
+                .is_none_or(|result| record.result == *result)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveStats {
+    pub mv: Move,
+    pub san: String,
+    pub games: usize,
+    pub white_wins: usize,
+    pub draws: usize,
+    pub black_wins: usize,
+    // Score for the side making the move, draws counting half
+    pub score_percent: f64,
+    // Of the player making the move, over the games that have a rating
+    pub average_rating: Option<u32>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: usize,
+    // Games already in the database, or earlier in the same file
+    pub duplicates: usize,
+    pub positions: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct PositionRecord {
+    hash: u64,
+    game_id: u32,
+    ply: u16,
+    mv: u32,
+}
+
+impl PositionRecord {
+    fn to_bytes(self) -> [u8; POSITION_RECORD_SIZE as usize] {
+        let mut bytes = [0; POSITION_RECORD_SIZE as usize];
+        bytes[0..8].copy_from_slice(&self.hash.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.game_id.to_le_bytes());
+        bytes[12..14].copy_from_slice(&self.ply.to_le_bytes());
+        bytes[14..18].copy_from_slice(&self.mv.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> PositionRecord {
+        PositionRecord {
+            hash: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            game_id: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            ply: u16::from_le_bytes(bytes[12..14].try_into().unwrap()),
+            mv: u32::from_le_bytes(bytes[14..18].try_into().unwrap()),
+        }
+    }
+
+    // None at the end of the input, which a partly written record also counts as
+    fn read(reader: &mut impl Read) -> Result<Option<PositionRecord>, Error> {
+        let mut bytes = [0; POSITION_RECORD_SIZE as usize];
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(PositionRecord::from_bytes(&bytes))),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+// Run files of an import in progress, removed however the import ends
+struct TempFiles(Vec<PathBuf>);
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// A directory holding imported games, their tags and an index of every position reached
+pub struct GameDatabase {
+    dir: PathBuf,
+    games: Vec<GameRecord>,
+}
+
+impl GameDatabase {
+    // Creates the directory and its files if they don't exist yet
+    pub fn open(dir: &str) -> Result<GameDatabase, Error> {
+        fs::create_dir_all(dir)?;
+        for file in [GAMES_FILE, GAME_INDEX_FILE, POSITION_INDEX_FILE] {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(Path::new(dir).join(file))?;
+        }
+        Self::open_existing(dir)
+    }
+
+    // Opens a database for reading only, without creating or changing anything in `dir`
+    pub fn open_existing(dir: &str) -> Result<GameDatabase, Error> {
+        let dir = PathBuf::from(dir);
+        if !dir.is_dir() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("no game database directory at {}", dir.display()),
+            ));
+        }
+
+        let mut games = Vec::new();
+        for line in BufReader::new(File::open(dir.join(GAME_INDEX_FILE))?).lines() {
+            let line = line?;
+            if !line.is_empty() {
+                games.push(parse_game_record(games.len() as u32, &line)?);
+            }
+        }
+        Ok(GameDatabase { dir, games })
+    }
+
+    pub fn games(&self) -> &[GameRecord] {
+        &self.games
+    }
+
+    pub fn filter_games(&self, filter: &GameFilter) -> Vec<&GameRecord> {
+        self.games
+            .iter()
+            .filter(|record| filter.matches(record))
+            .collect()
+    }
+
+    pub fn load_game(&self, record: &GameRecord) -> Result<PgnGame, Error> {
+        let mut file = File::open(self.dir.join(GAMES_FILE))?;
+        file.seek(SeekFrom::Start(record.offset))?;
+        let mut text = String::new();
+        file.take(record.length).read_to_string(&mut text)?;
+        let raw_game = RawPgnGame {
+            offset: record.offset,
+            line: 1,
+            text,
+        };
+        Ok(raw_game.parse()?)
+    }
+
+    // Games already in the database are skipped, so importing a file again adds nothing.
+    // The index is written last: until it lists the new games, nothing else refers to them.
+    pub fn import_pgn(&mut self, filepath: &str) -> Result<ImportSummary, Error> {
+        self.import_pgn_in_runs(filepath, POSITION_RUN_SIZE)
+    }
+
+    fn import_pgn_in_runs(
+        &mut self,
+        filepath: &str,
+        run_size: usize,
+    ) -> Result<ImportSummary, Error> {
+        let mut raw_games = PgnReader::open(filepath)?.raw_games();
+        let mut summary = ImportSummary::default();
+        let first_id = self.games.len() as u32;
+        let mut fingerprints: HashSet<u64> =
+            self.games.iter().map(|record| record.fingerprint).collect();
+
+        // Text after the last indexed game is left over from an import that didn't finish
+        let mut offset = self
+            .games
+            .last()
+            .map_or(0, |record| record.offset + record.length);
+        let games_file = OpenOptions::new()
+            .append(true)
+            .open(self.dir.join(GAMES_FILE))?;
+        games_file.set_len(offset)?;
+        let mut games_file = BufWriter::new(games_file);
+
+        let mut new_games = Vec::new();
+        let mut run = Vec::new();
+        let mut runs = TempFiles(Vec::new());
+
+        loop {
+            let batch = raw_games
+                .by_ref()
+                .take(IMPORT_BATCH_SIZE)
+                .collect::<Result<Vec<RawPgnGame>, Error>>()?;
+            if batch.is_empty() {
+                break;
+            }
+
+            // Replaying the games is the slow part, so it is done in parallel
+            let replayed: Vec<Option<(PgnGame, GamePositions)>> = batch
+                .par_iter()
+                .map(|raw_game| {
+                    let game = raw_game.parse().ok()?;
+                    let positions = replay_positions(&game)?;
+                    Some((game, positions))
+                })
+                .collect();
+
+            for (raw_game, replayed) in batch.iter().zip(replayed) {
+                let (game, positions) = match replayed {
+                    Some(replayed) => replayed,
+                    None => {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                };
+                if !fingerprints.insert(game_fingerprint(&game)) {
+                    summary.duplicates += 1;
+                    continue;
+                }
+
+                let text = format!("{}\n\n", raw_game.text.trim());
+                let id = first_id + new_games.len() as u32;
+                let record = game_record(id, offset, &text, &game);
+                games_file.write_all(text.as_bytes())?;
+                offset += text.len() as u64;
+
+                summary.positions += positions.len();
+                for (ply, (hash, mv)) in positions.into_iter().enumerate() {
+                    run.push(PositionRecord {
+                        hash,
+                        game_id: id,
+                        ply: ply.min(u16::MAX as usize) as u16,
+                        mv,
+                    });
+                    if run.len() >= run_size {
+                        runs.0.push(self.write_run(&mut run, runs.0.len())?);
+                    }
+                }
+                new_games.push(record);
+                summary.imported += 1;
+            }
+        }
+        games_file.into_inner()?.sync_all()?;
+        if new_games.is_empty() {
+            return Ok(summary);
+        }
+        if !run.is_empty() {
+            runs.0.push(self.write_run(&mut run, runs.0.len())?);
+        }
+
+        self.merge_positions(&runs.0, first_id)?;
+        self.write_game_index(&new_games)?;
+        self.games.extend(new_games);
+        Ok(summary)
+    }
+
+    // Sorts the positions and writes them to a temporary file for merging
+    fn write_run(&self, run: &mut Vec<PositionRecord>, number: usize) -> Result<PathBuf, Error> {
+        run.par_sort_unstable();
+        let path = self
+            .dir
+            .join(format!("{}.run{}.tmp", POSITION_INDEX_FILE, number));
+        let mut output = BufWriter::new(File::create(&path)?);
+        for record in run.drain(..) {
+            output.write_all(&record.to_bytes())?;
+        }
+        output.flush()?;
+        Ok(path)
+    }
+
+    // Positions are kept sorted by hash so lookups can binary search the file. The index and
+    // the runs are streamed through a k-way merge into a new file that replaces the old one.
+    // Positions of games from `first_id` on in the old index belong to an unfinished import.
+    fn merge_positions(&self, runs: &[PathBuf], first_id: u32) -> Result<(), Error> {
+        let path = self.dir.join(POSITION_INDEX_FILE);
+        let mut inputs = vec![BufReader::new(File::open(&path)?)];
+        for run in runs {
+            inputs.push(BufReader::new(File::open(run)?));
+        }
+        let next = |inputs: &mut [BufReader<File>], input: usize| loop {
+            match PositionRecord::read(&mut inputs[input])? {
+                Some(record) if input == 0 && record.game_id >= first_id => continue,
+                record => return Ok::<_, Error>(record),
+            }
+        };
+
+        let mut heap = BinaryHeap::new();
+        for input in 0..inputs.len() {
+            if let Some(record) = next(&mut inputs, input)? {
+                heap.push(Reverse((record, input)));
+            }
+        }
+
+        let tmp_path = self.dir.join(format!("{}.tmp", POSITION_INDEX_FILE));
+        let mut output = BufWriter::new(File::create(&tmp_path)?);
+        while let Some(Reverse((record, input))) = heap.pop() {
+            output.write_all(&record.to_bytes())?;
+            if let Some(record) = next(&mut inputs, input)? {
+                heap.push(Reverse((record, input)));
+            }
+        }
+        output.into_inner()?.sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+
+    // Commits an import by replacing the game index with one that lists the new games
+    fn write_game_index(&self, new_games: &[GameRecord]) -> Result<(), Error> {
+        let tmp_path = self.dir.join(format!("{}.tmp", GAME_INDEX_FILE));
+        let mut output = BufWriter::new(File::create(&tmp_path)?);
+        for record in self.games.iter().chain(new_games) {
+            writeln!(output, "{}", format_game_record(record))?;
+        }
+        output.into_inner()?.sync_all()?;
+        fs::rename(tmp_path, self.dir.join(GAME_INDEX_FILE))
+    }
+
+    fn position_records(&self, hash: u64) -> Result<Vec<PositionRecord>, Error> {
+        let mut file = File::open(self.dir.join(POSITION_INDEX_FILE))?;
+        let count = file.metadata()?.len() / POSITION_RECORD_SIZE;
+        let mut buffer = [0; POSITION_RECORD_SIZE as usize];
+        let mut read_record = |file: &mut File, index: u64| -> Result<PositionRecord, Error> {
+            file.seek(SeekFrom::Start(index * POSITION_RECORD_SIZE))?;
+            file.read_exact(&mut buffer)?;
+            Ok(PositionRecord::from_bytes(&buffer))
+        };
+
+        // First record with this hash
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let mid = (low + high) / 2;
+            if read_record(&mut file, mid)?.hash < hash {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let mut records = Vec::new();
+        let mut reader = BufReader::new(file);
+        reader.seek(SeekFrom::Start(low * POSITION_RECORD_SIZE))?;
+        for _ in low..count {
+            reader.read_exact(&mut buffer)?;
+            let record = PositionRecord::from_bytes(&buffer);
+            if record.hash != hash {
+                break;
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    // Games that reached the position, each listed once
+    pub fn games_with_position(
+        &self,
+        position: &Game,
+        filter: &GameFilter,
+    ) -> Result<Vec<&GameRecord>, Error> {
+        let mut ids: Vec<u32> = self
+            .position_records(position.position_hash())?
+            .iter()
+            .map(|record| record.game_id)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids
+            .into_iter()
+            .filter_map(|id| self.games.get(id as usize))
+            .filter(|record| filter.matches(record))
+            .collect())
+    }
+
+    // What was played from the position, most popular first
+    pub fn move_stats(
+        &self,
+        position: &Game,
+        filter: &GameFilter,
+    ) -> Result<Vec<MoveStats>, Error> {
+        let mut by_move: HashMap<u32, (MoveStats, u64, usize)> = HashMap::new();
+        let mover = position.next_player;
+
+        for record in self.position_records(position.position_hash())? {
+            if record.mv == NO_MOVE {
+                continue;
+            }
+            let game = match self.games.get(record.game_id as usize) {
+                Some(game) if filter.matches(game) => game,
+                _ => continue,
+            };
+            let mv = match position
+                .next_legal_moves
+                .iter()
+                .find(|mv| encode_move(mv, position.cols) == record.mv)
+            {
+                Some(mv) => *mv,
+                // A hash collision with some other position
+                None => continue,
+            };
+
+            let (stats, rating_sum, rated_games) = by_move.entry(record.mv).or_insert_with(|| {
+                (
+                    MoveStats {
+                        mv,
+                        san: position.move_to_san(mv).unwrap_or_default(),
+                        games: 0,
+                        white_wins: 0,
+                        draws: 0,
+                        black_wins: 0,
+                        score_percent: 0.,
+                        average_rating: None,
+                    },
+                    0,
+                    0,
+                )
+            });
+            stats.games += 1;
+            match game.result.as_str() {
+                "1-0" => stats.white_wins += 1,
+                "0-1" => stats.black_wins += 1,
+                "1/2-1/2" => stats.draws += 1,
+                _ => {}
+            }
+            let rating = match mover {
+                Color::White => game.white_elo,
+                Color::Black => game.black_elo,
+            };
+            if let Some(rating) = rating {
+                *rating_sum += rating as u64;
+                *rated_games += 1;
+            }
+        }
+
+        let mut stats: Vec<MoveStats> = by_move
+            .into_values()
+            .map(|(mut stats, rating_sum, rated_games)| {
+                let (wins, losses) = match mover {
+                    Color::White => (stats.white_wins, stats.black_wins),
+                    Color::Black => (stats.black_wins, stats.white_wins),
+                };
+                let decided = wins + losses + stats.draws;
+                if decided > 0 {
+                    stats.score_percent =
+                        100. * (wins as f64 + stats.draws as f64 / 2.) / decided as f64;
+                }
+                if rated_games > 0 {
+                    stats.average_rating = Some((rating_sum / rated_games as u64) as u32);
+                }
+                stats
+            })
+            .collect();
+        stats.sort_by(|a, b| b.games.cmp(&a.games).then_with(|| a.san.cmp(&b.san)));
+        Ok(stats)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+// Positions of the main line. None if any move is illegal.
+fn replay_positions(game: &PgnGame) -> Option<GamePositions> {
+    let mut position = game.starting_position().ok()?;
+    let mut positions = Vec::new();
+    for node in &game.moves {
+        let mv = position.move_from_san(&node.san)?;
+        positions.push((position.position_hash(), encode_move(&mv, position.cols)));
+        position.make_search_move(mv);
+    }
+    positions.push((position.position_hash(), NO_MOVE));
+    Some(positions)
+}
+
+// From square, to square and the letter of a promoted or dropped piece, a byte each.
+// Squares are numbered row by row, which fits any board up to 16x16.
+fn encode_move(mv: &Move, cols: usize) -> u32 {
+    let square = |(row, col): (usize, usize)| (row * cols + col) as u32;
+    let piece = match mv {
+        Move::Promotion(mv) => piece_to_san_char(mv.new_piece.piece_type) as u32,
+        Move::Drop(mv) => piece_to_san_char(mv.piece.piece_type) as u32,
+        _ => 0,
+    };
+    square(mv.get_from_position()) | square(mv.get_to_position()) << 8 | piece << 16
+}
+
+// FNV-1a over the Seven Tag Roster, the starting position and the main line, which unlike
+// the std hashers stays the same between builds
+fn game_fingerprint(game: &PgnGame) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let fields = SEVEN_TAG_ROSTER
+        .iter()
+        .chain(&["FEN"])
+        .map(|name| game.tag(name).unwrap_or(""))
+        .chain(game.moves.iter().map(|node| node.san.as_str()));
+    for field in fields {
+        for byte in field.bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+fn game_record(id: u32, offset: u64, text: &str, game: &PgnGame) -> GameRecord {
+    let tag = |name: &str| game.tag(name).unwrap_or("?").to_string();
+    GameRecord {
+        id,
+        offset,
+        length: text.len() as u64,
+        white: tag("White"),
+        black: tag("Black"),
+        event: tag("Event"),
+        date: game.tag("Date").unwrap_or("????.??.??").to_string(),
+        result: game.result.clone(),
+        white_elo: game.tag("WhiteElo").and_then(|elo| elo.parse().ok()),
+        black_elo: game.tag("BlackElo").and_then(|elo| elo.parse().ok()),
+        fingerprint: game_fingerprint(game),
+    }
+}
+
+fn format_game_record(record: &GameRecord) -> String {
+    let clean = |field: &str| field.replace(['\t', '\n'], " ");
+    let elo = |elo: Option<u32>| elo.map_or(String::new(), |elo| elo.to_string());
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:016x}",
+        record.offset,
+        record.length,
+        clean(&record.white),
+        clean(&record.black),
+        clean(&record.event),
+        clean(&record.date),
+        record.result,
+        elo(record.white_elo),
+        elo(record.black_elo),
+        record.fingerprint
+    )
+}
+
+fn parse_game_record(id: u32, line: &str) -> Result<GameRecord, Error> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 10 {
+        return Err(Error::other(format!(
+            "Corrupt game index entry for game {}",
+            id
+        )));
+    }
+    let number = |field: &str| {
+        field
+            .parse::<u64>()
+            .map_err(|_| Error::other(format!("Corrupt game index entry for game {}", id)))
+    };
+    Ok(GameRecord {
+        id,
+        offset: number(fields[0])?,
+        length: number(fields[1])?,
+        white: fields[2].to_string(),
+        black: fields[3].to_string(),
+        event: fields[4].to_string(),
+        date: fields[5].to_string(),
+        result: fields[6].to_string(),
+        white_elo: fields[7].parse().ok(),
+        black_elo: fields[8].parse().ok(),
+        fingerprint: u64::from_str_radix(fields[9], 16)
+            .map_err(|_| Error::other(format!("Corrupt game index entry for game {}", id)))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::VARIANTS;
+
+    const GAMES: &str = "[White \"Anna\"]\n[Black \"Ben\"]\n[Result \"1-0\"]\n[WhiteElo \"2100\"]\n\n\
+        1. e4 e5 2. Nf3 1-0\n\n\
+        [White \"Carl\"]\n[Black \"Anna\"]\n[Result \"1/2-1/2\"]\n\n1. e4 c5 1/2-1/2\n\n\
+        [White \"Ben\"]\n[Black \"Carl\"]\n[Result \"0-1\"]\n\n1. d4 d5 0-1\n\n\
+        [White \"Dora\"]\n[Black \"Ben\"]\n[Result \"*\"]\n\n1. e4 e4 *\n\n";
+
+    // A fresh directory holding the database and a PGN file with `pgn` in it
+    fn database(name: &str, pgn: &str) -> (GameDatabase, String) {
+        let dir = std::env::temp_dir().join(format!("game_db_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        let database = GameDatabase::open(dir.to_str().unwrap()).unwrap();
+        let pgn_path = dir.join("import.pgn");
+        fs::write(&pgn_path, pgn).unwrap();
+        (database, pgn_path.to_str().unwrap().to_string())
+    }
+
+    fn position_file(database: &GameDatabase) -> Vec<u8> {
+        fs::read(database.path().join(POSITION_INDEX_FILE)).unwrap()
+    }
+
+    fn after(sans: &[&str]) -> Game {
+        let mut game = Game::from_standard_board();
+        for san in sans {
+            let mv = game.move_from_san(san).unwrap();
+            game.make_move(mv);
+        }
+        game
+    }
+
+    #[test]
+    fn imports_games_and_looks_up_positions() {
+        let (mut database, pgn) = database("lookup", GAMES);
+        let summary = database.import_pgn(&pgn).unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                imported: 3,
+                skipped: 1,
+                duplicates: 0,
+                positions: 4 + 3 + 3,
+            }
+        );
+        assert_eq!(
+            position_file(&database).len() as u64,
+            10 * POSITION_RECORD_SIZE
+        );
+
+        let stats = database
+            .move_stats(&Game::from_standard_board(), &GameFilter::default())
+            .unwrap();
+        let counts: Vec<(&str, usize)> = stats
+            .iter()
+            .map(|stats| (stats.san.as_str(), stats.games))
+            .collect();
+        assert_eq!(counts, [("e4", 2), ("d4", 1)]);
+        assert_eq!(stats[0].white_wins + stats[0].draws, 2);
+        assert_eq!(stats[0].score_percent, 75.);
+        assert_eq!(stats[0].average_rating, Some(2100));
+
+        let games = database
+            .games_with_position(&after(&["e4"]), &GameFilter::default())
+            .unwrap();
+        assert_eq!(games.len(), 2);
+        let filter = GameFilter {
+            player: Some("carl".to_string()),
+            ..Default::default()
+        };
+        let games = database
+            .games_with_position(&after(&["e4"]), &filter)
+            .unwrap();
+        assert_eq!(games.len(), 1);
+        let game = database.load_game(games[0]).unwrap();
+        assert_eq!(game.tag("White"), Some("Carl"));
+        assert_eq!(game.main_line(), ["e4", "c5"]);
+
+        // The game index is read back the same when the database is opened again
+        let reopened = GameDatabase::open_existing(database.path().to_str().unwrap()).unwrap();
+        assert_eq!(reopened.games(), database.games());
+        fs::remove_dir_all(database.path()).unwrap();
+    }
+
+    #[test]
+    fn open_existing_leaves_a_missing_directory_alone() {
+        let dir = std::env::temp_dir().join(format!("game_db_{}_missing", std::process::id()));
+        assert!(GameDatabase::open_existing(dir.to_str().unwrap()).is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn importing_the_same_games_again_adds_nothing() {
+        let (mut database, pgn) = database("duplicates", GAMES);
+        database.import_pgn(&pgn).unwrap();
+        let positions = position_file(&database);
+
+        let summary = database.import_pgn(&pgn).unwrap();
+        assert_eq!((summary.imported, summary.duplicates), (0, 3));
+        assert_eq!(database.games().len(), 3);
+        assert_eq!(position_file(&database), positions);
+        fs::remove_dir_all(database.path()).unwrap();
+    }
+
+    #[test]
+    fn positions_merged_from_many_runs_match_a_single_run() {
+        let (mut single, pgn) = database("single_run", GAMES);
+        let (mut many, _) = database("many_runs", GAMES);
+        single.import_pgn(&pgn).unwrap();
+        many.import_pgn_in_runs(&pgn, 3).unwrap();
+        assert_eq!(position_file(&many), position_file(&single));
+
+        // Leaves no run files behind
+        let leftovers = fs::read_dir(many.path())
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("tmp".as_ref()))
+            .count();
+        assert_eq!(leftovers, 0);
+        fs::remove_dir_all(single.path()).unwrap();
+        fs::remove_dir_all(many.path()).unwrap();
+    }
+
+    #[test]
+    fn drops_what_an_unfinished_import_left_behind() {
+        let (mut database, pgn) = database("unfinished", GAMES);
+        database.import_pgn(&pgn).unwrap();
+
+        // Game text and positions of a game the index never listed
+        let mut games_file = OpenOptions::new()
+            .append(true)
+            .open(database.path().join(GAMES_FILE))
+            .unwrap();
+        games_file
+            .write_all(b"[White \"Lost\"]\n\n1. c4 *\n\n")
+            .unwrap();
+        let stale = PositionRecord {
+            hash: Game::from_standard_board().position_hash(),
+            game_id: 3,
+            ply: 0,
+            mv: 0,
+        };
+        let mut positions = position_file(&database);
+        positions.extend(stale.to_bytes());
+        fs::write(database.path().join(POSITION_INDEX_FILE), positions).unwrap();
+
+        fs::write(&pgn, "[White \"Eve\"]\n\n1. Nf3 *\n\n").unwrap();
+        database.import_pgn(&pgn).unwrap();
+        let games = database
+            .games_with_position(&Game::from_standard_board(), &GameFilter::default())
+            .unwrap();
+        assert_eq!(games.len(), 4);
+        let record = &database.games()[3];
+        assert_eq!(
+            record.offset,
+            database.games()[2].offset + database.games()[2].length
+        );
+        assert_eq!(
+            database.load_game(record).unwrap().tag("White"),
+            Some("Eve")
+        );
+        assert_eq!(
+            position_file(&database).len() as u64,
+            12 * POSITION_RECORD_SIZE
+        );
+        fs::remove_dir_all(database.path()).unwrap();
+    }
+
+    #[test]
+    fn move_encoding_tells_moves_apart_on_every_board_size() {
+        for variant in VARIANTS {
+            let game = Game::from_variant(variant);
+            let mut codes: Vec<u32> = game
+                .next_legal_moves
+                .iter()
+                .map(|mv| encode_move(mv, game.cols))
+                .collect();
+            codes.sort_unstable();
+            codes.dedup();
+            assert_eq!(
+                codes.len(),
+                game.next_legal_moves.len(),
+                "{}",
+                variant.name()
+            );
+        }
+    }
+}
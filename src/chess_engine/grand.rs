@@ -0,0 +1,105 @@
+use super::capablanca::CAPABLANCA_PIECES;
+use super::game::{Color, Game};
+use super::piece::{PieceDefinition, PieceType};
+use super::variant::Variant;
+
+// How many of each piece a side starts with, and so can get back by promoting
+const START_PIECES: [(PieceType, usize); 6] = [
+    (PieceType::Knight, 2),
+    (PieceType::Bishop, 2),
+    (PieceType::Rook, 2),
+    (PieceType::Queen, 1),
+    (PieceType::Fairy('A'), 1),
+    (PieceType::Fairy('C'), 1),
+];
+
+// Rows before the last one pawns may promote on
+const PROMOTION_ZONE_DEPTH: usize = 2;
+
+// Grand chess, on a 10x10 board without castling. Pawns start on the third rank and may
+// promote on the last three, but only to a piece their side has lost. On the last rank
+// they have to promote, so they can't move there while nothing has been lost.
+#[derive(Debug)]
+pub struct Grand;
+
+impl Variant for Grand {
+    fn name(&self) -> &'static str {
+        "Grand"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "r8r/1nbqkcabn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKCABN1/R8R w - - 0 1"
+    }
+
+    fn fairy_pieces(&self) -> &[PieceDefinition] {
+        &*CAPABLANCA_PIECES
+    }
+
+    // Pawns start on the third rank
+    fn pawn_rows(&self) -> usize {
+        2
+    }
+
+    fn promotions(&self, game: &Game, color: Color, to_row: usize) -> (Vec<PieceType>, bool) {
+        let last_row = game.last_row(color);
+        if to_row.abs_diff(last_row) > PROMOTION_ZONE_DEPTH {
+            return (Vec::new(), true);
+        }
+        let lost: Vec<PieceType> = START_PIECES
+            .iter()
+            .filter(|&&(piece_type, count)| {
+                let on_board = game
+                    .board
+                    .iter()
+                    .flatten()
+                    .filter(|place| {
+                        place.is_some_and(|p| p.piece_type == piece_type && p.color == color)
+                    })
+                    .count();
+                on_board < count
+            })
+            .map(|&(piece_type, _)| piece_type)
+            .collect();
+        (lost, to_row != last_row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FenValidation;
+
+    fn grand(fen: &str) -> Game {
+        Game::from_fen_str_validated(fen, &Grand, FenValidation::Strict)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn pawns_start_on_the_third_rank() {
+        let game = Game::from_variant(&Grand);
+        assert_eq!(game.to_full_fen_str(), Grand.start_fen());
+        assert_eq!(game.pawn_row(Color::White), 2);
+        assert_eq!(game.pawn_row(Color::Black), 7);
+        assert!(game.move_from_san("e5").is_some());
+        assert_eq!(game.move_from_san("e6"), None);
+
+        // The same board in standard chess has its pawns on the second rank
+        let standard = Game::from_fen_str("4k5/10/10/10/10/10/10/10/10/4K5 w - - 0 1").unwrap();
+        assert_eq!(standard.pawn_row(Color::White), 1);
+    }
+
+    #[test]
+    fn pawns_promote_only_to_lost_pieces() {
+        // With every piece still on the board the pawn can't go to the last rank
+        let game = grand("7k2/4P5/10/10/10/10/10/10/10/RNBQKCABNR w - - 0 1");
+        assert!(game.moves_from_square((8, 4)).is_empty());
+
+        // Without its archbishop White can only get that back
+        let game = grand("7k2/4P5/10/10/10/10/10/10/10/RNBQKC1BNR w - - 0 1");
+        assert!(game.move_from_san("e10=A").is_some());
+        for san in ["e10=Q", "e10=C", "e10"] {
+            assert_eq!(game.move_from_san(san), None, "{}", san);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use super::game::{Color, Game, GameState};
+use super::validate::PositionError;
+use super::variant::Variant;
+
+// White has a horde of 36 pawns and no king. White wins by mating, Black by capturing every
+// white piece.
+#[derive(Debug)]
+pub struct Horde;
+
+impl Variant for Horde {
+    fn name(&self) -> &'static str {
+        "Horde"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+    }
+
+    fn decisive_state(&self, after: &Game, color: Color, _gives_check: bool) -> Option<GameState> {
+        let horde_captured = !after
+            .board
+            .iter()
+            .flatten()
+            .any(|place| place.is_some_and(|p| p.color == Color::White));
+        (color == Color::Black && horde_captured).then_some(GameState::VariantWin(Color::Black))
+    }
+
+    // The horde has no king, and its pawns start on the first rank
+    fn allows_position_error(&self, error: &PositionError) -> bool {
+        matches!(
+            error,
+            PositionError::KingCount(Color::White, 0)
+                | PositionError::PawnOnBackRank(Color::White, (0, _))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+    use crate::validate::FenValidation;
+
+    fn horde(fen: &str) -> Game {
+        Game::from_fen_str_validated(fen, &Horde, FenValidation::Strict)
+            .unwrap()
+            .0
+    }
+
+    fn after(game: &Game, san: &str) -> Game {
+        let mut game = game.clone();
+        game.make_move(game.move_from_san(san).expect("Test move is legal"));
+        game
+    }
+
+    #[test]
+    fn start_position_and_first_rank_pawns() {
+        // Only the pawns in front of a gap can move at first
+        assert_eq!(Game::from_variant(&Horde).next_legal_moves.len(), 8);
+
+        // A pawn on the first rank can still move two squares
+        let game = horde("4k3/8/8/8/8/8/8/P7 w - - 0 1");
+        for san in ["a2", "a3"] {
+            assert!(game.move_from_san(san).is_some(), "{}", san);
+        }
+    }
+
+    #[test]
+    fn black_wins_by_capturing_the_whole_horde() {
+        let game = horde("4k3/8/8/8/8/8/3P4/3r4 b - - 0 1");
+        assert_eq!(
+            after(&game, "Rxd2").state,
+            GameState::VariantWin(Color::Black)
+        );
+        // Taking one of two pieces is just a capture
+        let game = horde("4k3/8/8/8/8/8/3P3P/3r4 b - - 0 1");
+        assert_eq!(after(&game, "Rxd2").state, GameState::Normal);
+    }
+
+    #[test]
+    fn white_wins_by_mate() {
+        let game = horde("7k/6pp/8/8/8/8/8/Q7 w - - 0 1");
+        assert_eq!(
+            after(&game, "Qa8#").state,
+            GameState::Checkmate(Color::Black)
+        );
+    }
+
+    #[test]
+    fn first_rank_double_steps_allow_no_en_passant() {
+        let game = after(&horde("4k3/8/8/8/8/1p6/8/P7 w - - 0 1"), "a3");
+        assert_eq!(game.to_full_fen_str(), "4k3/8/8/8/8/Pp6/8/8 b - - 0 1");
+        assert!(
+            !game
+                .next_legal_moves
+                .iter()
+                .any(|mv| matches!(mv, Move::EnPassant(_)))
+        );
+
+        // From the second rank it is a normal double step
+        let game = after(&horde("4k3/8/8/8/1p6/8/P7/8 w - - 0 1"), "a4");
+        assert_eq!(game.to_full_fen_str(), "4k3/8/8/8/Pp6/8/8/8 b - a3 0 1");
+        assert!(game.move_from_san("bxa3").is_some());
+    }
+}
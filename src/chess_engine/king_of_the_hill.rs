@@ -0,0 +1,53 @@
+use super::game::{Color, Game, GameState};
+use super::piece::PieceType;
+use super::variant::Variant;
+
+// d4, e4, d5 and e5
+pub const HILL_SQUARES: [(usize, usize); 4] = [(3, 3), (3, 4), (4, 3), (4, 4)];
+
+// Standard chess, except a king reaching one of the four centre squares wins
+#[derive(Debug)]
+pub struct KingOfTheHill;
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn decisive_state(&self, after: &Game, color: Color, _gives_check: bool) -> Option<GameState> {
+        let on_hill = HILL_SQUARES.iter().any(|&(row, col)| {
+            after.board[row][col]
+                .is_some_and(|p| p.piece_type == PieceType::King && p.color == color)
+        });
+        on_hill.then_some(GameState::VariantWin(color))
+    }
+
+    fn goal_squares(&self) -> Vec<(usize, usize)> {
+        HILL_SQUARES.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FenValidation;
+
+    #[test]
+    fn king_reaching_the_centre_wins() {
+        let (game, _) = Game::from_fen_str_validated(
+            "4k3/8/8/8/8/3K4/8/8 w - - 0 1",
+            &KingOfTheHill,
+            FenValidation::Strict,
+        )
+        .unwrap();
+        for (san, state) in [
+            ("Kd4", GameState::VariantWin(Color::White)),
+            ("Ke4", GameState::VariantWin(Color::White)),
+            ("Kc4", GameState::Normal),
+        ] {
+            let mut game = game.clone();
+            game.make_move(game.move_from_san(san).unwrap());
+            assert_eq!(game.state, state, "{}", san);
+        }
+    }
+}
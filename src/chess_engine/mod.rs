@@ -1,8 +1,26 @@
+pub mod antichess;
+pub mod atomic;
+pub mod capablanca;
+pub mod crazyhouse;
+pub mod eco;
+pub mod epd;
 pub mod game;
+pub mod game_database;
+pub mod grand;
+pub mod horde;
+pub mod king_of_the_hill;
 pub mod moves;
 pub mod pgn_move_conversion;
+pub mod pgn_parse;
 pub mod pgn_read;
-pub mod pgn_test;
+pub mod pgn_validate;
+pub mod pgn_write;
 pub mod piece;
 pub mod piece_moves;
+pub mod racing_kings;
 pub mod read_fen_string;
+pub mod search;
+pub mod three_check;
+pub mod validate;
+pub mod variant;
+pub mod zobrist;
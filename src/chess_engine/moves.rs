@@ -14,6 +14,7 @@ pub enum Move {
     Castles(CastlesMove),
     Normal(NormalMove),
     Promotion(PromotionMove),
+    Drop(DropMove),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
@@ -30,6 +31,15 @@ pub struct PromotionMove {
 pub struct CastlesMove {
     pub color: Color,
     pub side: CastleSide,
+    pub row: usize,
+    // Starting columns, which only differ from e and a/h in Chess960
+    pub king_col: usize,
+    pub rook_col: usize,
+    // Where the king and rook end up
+    pub king_to: usize,
+    pub rook_to: usize,
+    // The king in the middle and the rook in the corner, as in standard chess
+    pub classical: bool,
     pub game_state: GameState,
 }
 
@@ -41,6 +51,14 @@ pub struct EnPassantMove {
     pub game_state: GameState,
 }
 
+// A piece from the pocket placed on an empty square, in Crazyhouse
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
+pub struct DropMove {
+    pub piece: Piece,
+    pub to_position: (usize, usize),
+    pub game_state: GameState,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
 pub struct NormalMove {
     pub piece: Piece,
@@ -57,6 +75,7 @@ impl Move {
             Move::Castles(mv) => mv.game_state = state,
             Move::EnPassant(mv) => mv.game_state = state,
             Move::Promotion(mv) => mv.game_state = state,
+            Move::Drop(mv) => mv.game_state = state,
         };
     }
 
@@ -66,19 +85,27 @@ impl Move {
             Move::Castles(mv) => mv.game_state,
             Move::EnPassant(mv) => mv.game_state,
             Move::Promotion(mv) => mv.game_state,
+            Move::Drop(mv) => mv.game_state,
+        }
+    }
+
+    pub fn is_capture(&self) -> bool {
+        match self {
+            Move::Normal(mv) => mv.capture.is_some(),
+            Move::Promotion(mv) => mv.capture.is_some(),
+            Move::EnPassant(_) => true,
+            Move::Castles(_) | Move::Drop(_) => false,
         }
     }
 
     pub fn get_from_position(&self) -> (usize, usize) {
         match self {
             Move::Normal(mv) => mv.from_position,
-            Move::Castles(mv) => {
-                let row = if mv.color == Color::White { 0 } else { 7 };
-                let col = 4;
-                (row, col)
-            }
+            Move::Castles(mv) => (mv.row, mv.king_col),
             Move::EnPassant(mv) => mv.from_position,
             Move::Promotion(mv) => mv.from_position,
+            // Drops don't come from a square, so they start and end on the same one
+            Move::Drop(mv) => mv.to_position,
         }
     }
 
@@ -86,18 +113,23 @@ impl Move {
         match self {
             Move::Normal(mv) => mv.to_position,
             Move::Castles(mv) => {
-                let row = if mv.color == Color::White { 0 } else { 7 };
-                let col = if mv.side == CastleSide::King { 6 } else { 2 };
-                (row, col)
+                // Chess960 castling is entered as the king taking its own rook, since the
+                // king may not move at all or only move one square
+                if mv.classical {
+                    (mv.row, mv.king_to)
+                } else {
+                    (mv.row, mv.rook_col)
+                }
             }
             Move::EnPassant(mv) => mv.to_position,
             Move::Promotion(mv) => mv.to_position,
+            Move::Drop(mv) => mv.to_position,
         }
     }
 }
 
 impl Game {
-    fn get_all_moves(&self, color: Color) -> Vec<Move> {
+    pub(crate) fn get_all_moves(&self, color: Color) -> Vec<Move> {
         let mut all_moves: Vec<Move> = Vec::new();
 
         for i in 0..self.board.len() {
@@ -105,7 +137,7 @@ impl Game {
                 if let Some(piece) = self.board[i][j]
                     && piece.color == color
                 {
-                    let mut piece_moves = self.get_piece_moves((i, j));
+                    let mut piece_moves = self.variant.piece_moves(self, (i, j));
                     all_moves.append(&mut piece_moves);
                 }
             }
@@ -114,77 +146,59 @@ impl Game {
     }
 
     fn get_piece_legal_moves(&self, from_position: (usize, usize), check_next: bool) -> Vec<Move> {
-        let mut piece_legal_moves: Vec<Move> = Vec::new();
         let piece = match self.board[from_position.0][from_position.1] {
             Some(p) => p,
-            None => return piece_legal_moves,
+            None => return Vec::new(),
         };
-        let mut candidate_moves: Vec<Move> = self.get_piece_moves(from_position);
+        let candidate_moves: Vec<Move> = self.variant.piece_moves(self, from_position);
+        self.filter_legal_moves(candidate_moves, piece.color, check_next)
+    }
 
+    // Keep the moves of `color` the variant allows, setting the state each one leads to
+    fn filter_legal_moves(
+        &self,
+        mut candidate_moves: Vec<Move>,
+        color: Color,
+        check_next: bool,
+    ) -> Vec<Move> {
+        let mut legal_moves: Vec<Move> = Vec::new();
         for mv in &mut candidate_moves {
             let mut copyboard = self.clone();
             // Simulate the next move
             copyboard.make_move_helper(*mv, false);
 
-            // If castling, place some kings to check if can castle
-            match mv {
-                Move::Castles(castles_mv) => {
-                    let new_king = Piece {
-                        color: piece.color,
-                        piece_type: PieceType::King,
-                        has_moved: true,
-                    };
-                    let row: usize = match castles_mv.color {
-                        Color::White => 0,
-                        Color::Black => 7,
-                    };
-                    match castles_mv.side {
-                        CastleSide::King => {
-                            copyboard.board[row][4] = Some(new_king);
-                            copyboard.board[row][5] = Some(new_king);
-                        }
-                        CastleSide::Queen => {
-                            copyboard.board[row][4] = Some(new_king);
-                            copyboard.board[row][3] = Some(new_king);
-                        }
-                    }
-                }
-                _ => {}
-            };
-
-            // If we are in check after the move, it's not legal and add all the next moves to the dict
-            if copyboard.in_check(piece.color) {
+            if !self.variant.is_legal(&mut copyboard, *mv, color) {
                 continue;
             }
 
-            // Replace the new kings with previous things if castled
-            copyboard.make_move_only(*mv);
-
-            let in_check = copyboard.in_check(piece.color.opposite());
+            let opponent = color.opposite();
+            let in_check = self.variant.in_check(&mut copyboard, opponent);
 
             if in_check {
-                mv.set_state(GameState::InCheck(piece.color.opposite()));
+                mv.set_state(GameState::InCheck(opponent));
             }
 
-            if check_next {
+            if let Some(state) = self.variant.decisive_state(&copyboard, color, in_check) {
+                mv.set_state(state);
+            } else if check_next {
                 let next_moves = copyboard.get_all_legal_moves(false);
                 if next_moves.len() == 0 {
-                    if in_check {
-                        mv.set_state(GameState::Checkmate(piece.color.opposite()));
-                    } else {
-                        mv.set_state(GameState::Stalemate);
-                    }
+                    mv.set_state(self.variant.no_moves_state(opponent, in_check));
                 }
             }
 
-            piece_legal_moves.push(*mv);
+            legal_moves.push(*mv);
         }
-        piece_legal_moves
+        legal_moves
     }
 
     pub fn get_all_legal_moves(&self, check_next: bool) -> Vec<Move> {
+        if self.state.is_over() {
+            return Vec::new();
+        }
         let color = self.next_player;
-        self.board
+        let legal_moves = self
+            .board
             .into_par_iter()
             .enumerate()
             .flat_map(|(i, row)| {
@@ -201,10 +215,42 @@ impl Game {
                     })
                     .collect::<Vec<_>>()
             })
-            .collect()
+            .chain(self.filter_legal_moves(
+                self.variant.extra_moves(self, color),
+                color,
+                check_next,
+            ))
+            .collect();
+        self.variant.restrict_moves(legal_moves)
     }
 
-    fn in_check(&mut self, color: Color) -> bool {
+    // Whether the king of `color` can be taken after it played `mv`. When castling, the
+    // squares the king passed through count too.
+    pub(crate) fn king_attacked_after(&mut self, mv: Move, color: Color) -> bool {
+        let variant = self.variant;
+        let Move::Castles(castles_mv) = mv else {
+            return variant.in_check(self, color);
+        };
+
+        // Place some kings along the way, then put the row back
+        let new_king = Piece {
+            color,
+            piece_type: PieceType::King,
+            has_moved: true,
+            promoted: false,
+        };
+        let row = castles_mv.row;
+        let saved_row = self.board[row];
+        let king_to = castles_mv.king_to;
+        for col in castles_mv.king_col.min(king_to)..=castles_mv.king_col.max(king_to) {
+            self.board[row][col] = Some(new_king);
+        }
+        let attacked = variant.in_check(self, color);
+        self.board[row] = saved_row;
+        attacked
+    }
+
+    pub(crate) fn in_check(&mut self, color: Color) -> bool {
         let opposite_moves = self.get_all_moves(color.opposite());
         for mv in &opposite_moves {
             match mv {
@@ -229,7 +275,54 @@ impl Game {
     }
 
     pub fn make_move(&mut self, mv: Move) {
+        self.undone_moves.clear();
+        self.make_move_helper(mv, true);
+    }
+
+    // Take back the last move, keeping it around so it can be redone
+    pub fn undo_move(&mut self) -> Option<Move> {
+        let mv = self.move_history.pop()?;
+        self.unmake_move_only(mv);
+        self.undone_moves.push(mv);
+
+        self.next_player = self.next_player.opposite();
+        self.state = match self.move_history.last() {
+            Some(last_move) => last_move.get_state(),
+            None => GameState::Normal,
+        };
+        self.next_legal_moves = self.get_all_legal_moves(true);
+        if self.move_history.is_empty() {
+            self.state = self.start_state();
+        }
+        Some(mv)
+    }
+
+    // The state of the position the game started from, which no move in the history
+    // records. Needs the legal moves to be up to date.
+    pub(crate) fn start_state(&mut self) -> GameState {
+        let color = self.next_player;
+        let variant = self.variant;
+        let in_check = variant.in_check(self, color);
+        if self.next_legal_moves.is_empty() {
+            variant.no_moves_state(color, in_check)
+        } else if in_check {
+            GameState::InCheck(color)
+        } else {
+            GameState::Normal
+        }
+    }
+
+    pub fn redo_move(&mut self) -> Option<Move> {
+        let mv = self.undone_moves.pop()?;
         self.make_move_helper(mv, true);
+        Some(mv)
+    }
+
+    // Cheaper than make_move for searching: legal replies are generated
+    // without looking a move further ahead for mates and stalemates
+    pub(crate) fn make_search_move(&mut self, mv: Move) {
+        self.make_move_helper(mv, false);
+        self.next_legal_moves = self.get_all_legal_moves(false);
     }
 
     fn make_move_helper(&mut self, mv: Move, check_next: bool) {
@@ -262,6 +355,42 @@ impl Game {
             Move::Promotion(pr_move) => {
                 self.make_promotion_move(pr_move);
             }
+            Move::Drop(drop_move) => {
+                self.board[drop_move.to_position.0][drop_move.to_position.1] =
+                    Some(drop_move.piece);
+                self.pocket_mut(drop_move.piece.color)
+                    .remove(drop_move.piece.piece_type);
+            }
+        }
+        let variant = self.variant;
+        variant.after_move(self, mv);
+    }
+
+    fn unmake_move_only(&mut self, mv: Move) {
+        let variant = self.variant;
+        variant.undo_move(self, mv);
+        match mv {
+            Move::Normal(normal_move) => {
+                self.board[normal_move.from_position.0][normal_move.from_position.1] =
+                    Some(normal_move.piece);
+                self.board[normal_move.to_position.0][normal_move.to_position.1] =
+                    normal_move.capture;
+            }
+            Move::Castles(castles_move) => {
+                self.unmake_castles_move(castles_move);
+            }
+            Move::EnPassant(ep_move) => {
+                self.unmake_enpassant_move(ep_move);
+            }
+            Move::Promotion(pr_move) => {
+                self.board[pr_move.from_position.0][pr_move.from_position.1] = Some(pr_move.piece);
+                self.board[pr_move.to_position.0][pr_move.to_position.1] = pr_move.capture;
+            }
+            Move::Drop(drop_move) => {
+                self.board[drop_move.to_position.0][drop_move.to_position.1] = None;
+                self.pocket_mut(drop_move.piece.color)
+                    .add(drop_move.piece.piece_type);
+            }
         }
     }
 
@@ -273,44 +402,42 @@ impl Game {
     }
 
     fn make_castles_move(&mut self, mv: CastlesMove) {
-        let king_starting_col: usize = 4;
-        let (king_ending_col, row, rook_starting_col, rook_ending_col): (
-            usize,
-            usize,
-            usize,
-            usize,
-        );
-        match mv.side {
-            CastleSide::King => {
-                king_ending_col = 6;
-                rook_starting_col = 7;
-                rook_ending_col = 5;
-            }
-            CastleSide::Queen => {
-                king_ending_col = 2;
-                rook_starting_col = 0;
-                rook_ending_col = 3;
-            }
-        };
-        match mv.color {
-            Color::Black => {
-                row = 7;
-            }
-            Color::White => {
-                row = 0;
-            }
-        };
-        self.board[row][king_starting_col] = None;
+        let row = mv.row;
+        let (king_ending_col, rook_ending_col) = (mv.king_to, mv.rook_to);
+        // Clear both first, in Chess960 the king may land where the rook was or vice versa
+        self.board[row][mv.king_col] = None;
+        self.board[row][mv.rook_col] = None;
         self.board[row][king_ending_col] = Some(Piece {
             piece_type: PieceType::King,
             color: mv.color,
             has_moved: true,
+            promoted: false,
         });
-        self.board[row][rook_starting_col] = None;
         self.board[row][rook_ending_col] = Some(Piece {
             piece_type: PieceType::Rook,
             color: mv.color,
             has_moved: true,
+            promoted: false,
+        });
+    }
+
+    fn unmake_castles_move(&mut self, mv: CastlesMove) {
+        let row = mv.row;
+        let (king_ending_col, rook_ending_col) = (mv.king_to, mv.rook_to);
+        // Castling is only possible if neither piece has moved before
+        self.board[row][king_ending_col] = None;
+        self.board[row][rook_ending_col] = None;
+        self.board[row][mv.king_col] = Some(Piece {
+            piece_type: PieceType::King,
+            color: mv.color,
+            has_moved: false,
+            promoted: false,
+        });
+        self.board[row][mv.rook_col] = Some(Piece {
+            piece_type: PieceType::Rook,
+            color: mv.color,
+            has_moved: false,
+            promoted: false,
         });
     }
 
@@ -327,11 +454,25 @@ impl Game {
         self.board[mv.pawn_capture_position.0][mv.pawn_capture_position.1] = None;
     }
 
+    fn unmake_enpassant_move(&mut self, mv: EnPassantMove) {
+        if let Some(piece) = self.board[mv.to_position.0][mv.to_position.1] {
+            self.board[mv.from_position.0][mv.from_position.1] = Some(piece);
+            self.board[mv.pawn_capture_position.0][mv.pawn_capture_position.1] = Some(Piece {
+                piece_type: PieceType::Pawn,
+                color: piece.color.opposite(),
+                has_moved: true,
+                promoted: false,
+            });
+        }
+        self.board[mv.to_position.0][mv.to_position.1] = None;
+    }
+
     pub fn moves_from_square(&self, position: (usize, usize)) -> Vec<Move> {
         self.next_legal_moves
             .iter()
             .filter_map(|mv| {
-                if mv.get_from_position() == position {
+                // Drops are picked from the pocket rather than a square
+                if mv.get_from_position() == position && !matches!(mv, Move::Drop(_)) {
                     Some(*mv)
                 } else {
                     None
@@ -340,3 +481,23 @@ impl Game {
             .collect::<Vec<_>>()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taking_back_to_a_fen_start_keeps_its_check() {
+        let mut game = Game::from_fen_str("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(game.state, GameState::InCheck(Color::Black));
+        game.make_move(game.move_from_san("Kd7").unwrap());
+        assert_eq!(game.state, GameState::Normal);
+        game.undo_move();
+        assert_eq!(game.state, GameState::InCheck(Color::Black));
+        assert_eq!(game.next_legal_moves.len(), 4);
+
+        // A FEN of a finished game starts finished
+        let game = Game::from_fen_str("k7/8/1Q6/8/8/8/8/7K b - - 0 1").unwrap();
+        assert_eq!(game.state, GameState::Stalemate);
+    }
+}
@@ -30,6 +30,95 @@ impl Game {
         output_map
     }
 
+    // Find the legal move a SAN string refers to. Check marks, annotations, missing
+    // promotion '=' and superfluous disambiguation are tolerated.
+    pub fn move_from_san(&self, san: &str) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+        if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+            let side = if san.len() == 3 {
+                CastleSide::King
+            } else {
+                CastleSide::Queen
+            };
+            return self
+                .next_legal_moves
+                .iter()
+                .copied()
+                .find(|mv| matches!(mv, Move::Castles(castles_mv) if castles_mv.side == side));
+        }
+
+        // Drops like N@f3, with the P optional for pawns
+        if let Some((piece, square)) = san.split_once('@') {
+            let piece_type = match piece {
+                "" | "P" => PieceType::Pawn,
+                _ => san_char_to_piece(self, piece.chars().next()?)?,
+            };
+            let square: Vec<char> = square.chars().collect();
+            let (to_position, square_len) = self.square_at_end(&square)?;
+            if square_len != square.len() {
+                return None;
+            }
+            return self.next_legal_moves.iter().copied().find(|mv| {
+                matches!(mv, Move::Drop(drop_mv)
+                    if drop_mv.piece.piece_type == piece_type && drop_mv.to_position == to_position)
+            });
+        }
+
+        let mut chars: Vec<char> = san.chars().filter(|chr| !"x-=:".contains(*chr)).collect();
+
+        let piece_type = match chars.first().and_then(|&chr| san_char_to_piece(self, chr)) {
+            Some(piece_type) => {
+                chars.remove(0);
+                piece_type
+            }
+            None => PieceType::Pawn,
+        };
+        let promotion = match chars.last().and_then(|&chr| san_char_to_piece(self, chr)) {
+            Some(new_piece) if piece_type == PieceType::Pawn => {
+                chars.pop();
+                Some(new_piece)
+            }
+            _ => None,
+        };
+        let (to_position, square_len) = self.square_at_end(&chars)?;
+        let disambiguation = &chars[..chars.len() - square_len];
+
+        let candidates: Vec<Move> = self
+            .next_legal_moves
+            .iter()
+            .copied()
+            .filter(|mv| {
+                let from_position = mv.get_from_position();
+                let (moving_piece, new_piece) = match mv {
+                    Move::Normal(mv) => (mv.piece.piece_type, None),
+                    Move::Promotion(mv) => (mv.piece.piece_type, Some(mv.new_piece.piece_type)),
+                    Move::EnPassant(_) => (PieceType::Pawn, None),
+                    Move::Castles(_) | Move::Drop(_) => return false,
+                };
+                let from_str = board_position_to_notation(from_position.0, from_position.1);
+                moving_piece == piece_type
+                    && mv.get_to_position() == to_position
+                    && new_piece == promotion
+                    && disambiguation.iter().all(|chr| from_str.contains(*chr))
+            })
+            .collect();
+
+        if candidates.len() == 1 {
+            Some(candidates[0])
+        } else {
+            None
+        }
+    }
+
+    // SAN of one of the next legal moves, with check and mate suffixes
+    pub fn move_to_san(&self, mv: Move) -> Option<String> {
+        self.mvs_to_str()
+            .into_iter()
+            .find(|(_, legal_move)| *legal_move == mv)
+            .map(|(san, _)| san)
+    }
+
     fn mv_to_str(&self, mv: Move) -> String {
         match mv {
             Move::Normal(mv) => {
@@ -48,11 +137,7 @@ impl Game {
                             ' '
                         }
                     }
-                    PieceType::Bishop => 'B',
-                    PieceType::Queen => 'Q',
-                    PieceType::Knight => 'N',
-                    PieceType::Rook => 'R',
-                    PieceType::King => 'K',
+                    piece_type => piece_to_san_char(piece_type),
                 };
                 return format!(
                     "{}{}{}",
@@ -82,13 +167,11 @@ impl Game {
                     game_state: GameState::Normal,
                 }));
                 let newpiece_char = match mv.new_piece.piece_type {
-                    PieceType::Bishop => 'B',
-                    PieceType::Knight => 'N',
-                    PieceType::Queen => 'Q',
-                    PieceType::Rook => 'R',
-                    _ => {
+                    PieceType::Pawn => {
                         panic!("Can't make this wtf")
                     }
+                    // Kings only in Antichess
+                    piece_type => piece_to_san_char(piece_type),
                 };
                 return format!("{}={}", normal_mv_str, newpiece_char);
             }
@@ -100,8 +183,67 @@ impl Game {
                 let to_position = board_position_to_notation(mv.to_position.0, mv.to_position.1);
                 return format!("{}x{}", from_file, to_position);
             }
+            Move::Drop(mv) => {
+                let to_position = board_position_to_notation(mv.to_position.0, mv.to_position.1);
+                return format!("{}@{}", piece_to_san_char(mv.piece.piece_type), to_position);
+            }
         };
     }
+
+    // The square the notation ends with, like e4 or b10, and how many chars it takes
+    fn square_at_end(&self, chars: &[char]) -> Option<((usize, usize), usize)> {
+        let digits = chars
+            .iter()
+            .rev()
+            .take_while(|chr| chr.is_ascii_digit())
+            .count();
+        if digits == 0 || digits == chars.len() {
+            return None;
+        }
+        let file = chars[chars.len() - digits - 1];
+        let rank: String = chars[chars.len() - digits..].iter().collect();
+        let col = (file as u32).checked_sub('a' as u32)? as usize;
+        let row = rank.parse::<usize>().ok()?.checked_sub(1)?;
+        if self.valid_position((row, col)) {
+            Some(((row, col), digits + 1))
+        } else {
+            None
+        }
+    }
+
+    // The square written like e4 or b10, if it is on the board
+    pub fn notation_to_board_position(&self, notation: &str) -> Option<(usize, usize)> {
+        let chars: Vec<char> = notation.chars().collect();
+        match self.square_at_end(&chars)? {
+            (position, square_len) if square_len == chars.len() => Some(position),
+            _ => None,
+        }
+    }
+
+    // Long algebraic notation as used by UCI engines: e2e4, e7e8q, and P@e4 for drops.
+    // Castling is written as the king's move, or as king takes rook in Chess960.
+    pub fn move_to_uci(&self, mv: Move) -> String {
+        let (from, to) = (mv.get_from_position(), mv.get_to_position());
+        let to_str = board_position_to_notation(to.0, to.1);
+        match mv {
+            Move::Drop(mv) => format!("{}@{}", piece_to_san_char(mv.piece.piece_type), to_str),
+            Move::Promotion(mv) => format!(
+                "{}{}{}",
+                board_position_to_notation(from.0, from.1),
+                to_str,
+                piece_to_san_char(mv.new_piece.piece_type).to_ascii_lowercase()
+            ),
+            _ => format!("{}{}", board_position_to_notation(from.0, from.1), to_str),
+        }
+    }
+
+    // Find the legal move a UCI move string refers to
+    pub fn move_from_uci(&self, uci: &str) -> Option<Move> {
+        self.next_legal_moves
+            .iter()
+            .copied()
+            .find(|mv| self.move_to_uci(*mv).eq_ignore_ascii_case(uci))
+    }
 }
 
 fn deduplicate_moves(movestr: &str, mvs: Vec<Move>) -> Vec<(String, Move)> {
@@ -129,7 +271,7 @@ fn deduplicate_moves(movestr: &str, mvs: Vec<Move>) -> Vec<(String, Move)> {
         let condition = if !has_same_col {
             mv_notation.chars().nth(0).unwrap().to_string()
         } else if !has_same_row {
-            mv_notation.chars().nth(1).unwrap().to_string()
+            mv_notation[1..].to_string()
         } else {
             mv_notation
         };
@@ -141,6 +283,30 @@ fn deduplicate_moves(movestr: &str, mvs: Vec<Move>) -> Vec<(String, Move)> {
     output
 }
 
+pub(super) fn piece_to_san_char(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::Pawn => 'P',
+        PieceType::Knight => 'N',
+        PieceType::Bishop => 'B',
+        PieceType::Rook => 'R',
+        PieceType::Queen => 'Q',
+        PieceType::King => 'K',
+        PieceType::Fairy(letter) => letter,
+    }
+}
+
+// Fairy piece letters only count in a variant that has the piece
+fn san_char_to_piece(game: &Game, chr: char) -> Option<PieceType> {
+    match chr {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => game.fairy_piece(chr).map(PieceDefinition::piece_type),
+    }
+}
+
 fn game_state_to_str(state: GameState) -> String {
     match state {
         GameState::InCheck(_) => {
@@ -152,7 +318,7 @@ fn game_state_to_str(state: GameState) -> String {
         GameState::Checkmate(_) => {
             return "#".to_string();
         }
-        GameState::Stalemate => {
+        GameState::Stalemate | GameState::VariantWin(_) | GameState::VariantDraw => {
             return "".to_string();
         }
     }
@@ -0,0 +1,721 @@
+use super::game::Game;
+use super::variant::{Standard, variant_from_name};
+use std::{fmt, io, mem, time::Duration};
+
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+pub const GAME_TERMINATIONS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+// SAN of a null move, a pass written by ChessBase and other tools in analysis
+pub const NULL_MOVE: &str = "--";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnNode {
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comments_before: Vec<String>,
+    pub comments_after: Vec<String>,
+    // Remaining clock time from a [%clk h:mm:ss] comment annotation
+    pub clock: Option<Duration>,
+    // Alternatives to this move, each one a line starting in the same position
+    pub variations: Vec<Vec<PgnNode>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnNode>,
+    // Comments that don't belong to any move, e.g. in a game without moves
+    pub comments: Vec<String>,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(key, _)| key == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn main_line(&self) -> Vec<String> {
+        self.moves.iter().map(|node| node.san.clone()).collect()
+    }
+
+    // Position the game starts from, given by the SetUp/FEN and Variant tags
+    pub fn starting_position(&self) -> Result<Game, io::Error> {
+        let variant_name = self.tag("Variant").unwrap_or("Standard");
+        let chess960 = matches!(
+            variant_name.to_lowercase().as_str(),
+            "chess960" | "chess 960" | "fischerandom" | "fischer random"
+        );
+        let variant = if chess960 {
+            &Standard
+        } else {
+            variant_from_name(variant_name)
+                .ok_or_else(|| io::Error::other(format!("Unsupported variant {}", variant_name)))?
+        };
+
+        let fen = self.tag("FEN").unwrap_or(variant.start_fen());
+        let mut position = Game::read_fen(fen, variant)?.0;
+        position.chess960 |= chess960;
+        position.set_variant(variant);
+        Ok(position)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PgnParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for PgnParseError {}
+
+impl From<PgnParseError> for io::Error {
+    fn from(err: PgnParseError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    TagOpen,
+    TagClose,
+    Str(String),
+    Symbol(String),
+    MoveNumber,
+    Nag(u8),
+    Comment(String),
+    VariationOpen,
+    VariationClose,
+    Termination(String),
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::TagOpen => write!(f, "'['"),
+            TokenKind::TagClose => write!(f, "']'"),
+            TokenKind::Str(value) => write!(f, "string \"{}\"", value),
+            TokenKind::Symbol(symbol) => write!(f, "'{}'", symbol),
+            TokenKind::MoveNumber => write!(f, "move number"),
+            TokenKind::Nag(nag) => write!(f, "NAG ${}", nag),
+            TokenKind::Comment(_) => write!(f, "comment"),
+            TokenKind::VariationOpen => write!(f, "'('"),
+            TokenKind::VariationClose => write!(f, "')'"),
+            TokenKind::Termination(result) => write!(f, "result '{}'", result),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str) -> Self {
+        Lexer {
+            input,
+            pos: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let chr = self.peek_char()?;
+        self.pos += chr.len_utf8();
+        if chr == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(chr)
+    }
+
+    fn error(&self, line: usize, column: usize, message: String) -> PgnParseError {
+        PgnParseError {
+            line,
+            column,
+            message,
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(chr) = self.next_char() {
+            if chr == '\n' {
+                break;
+            }
+        }
+    }
+
+    // Throw away the rest of a broken game so parsing can carry on with the next one. That
+    // starts at an [Event tag after a blank line, so an error among the tags doesn't leave
+    // the rest of them to be read as a game of their own.
+    fn skip_to_next_game(&mut self) {
+        let mut after_blank_line = false;
+        loop {
+            if self.column == 1 {
+                let rest = &self.input[self.pos..];
+                if after_blank_line && rest.starts_with("[Event") {
+                    return;
+                }
+                let line_end = rest.find('\n').unwrap_or(rest.len());
+                after_blank_line = rest[..line_end].trim().is_empty();
+            }
+            if self.next_char().is_none() {
+                return;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnParseError> {
+        loop {
+            let (line, column) = (self.line, self.column);
+            let chr = match self.peek_char() {
+                Some(chr) => chr,
+                None => return Ok(None),
+            };
+
+            // Lines starting with % are escaped from parsing
+            if chr == '%' && column == 1 {
+                self.skip_line();
+                continue;
+            }
+            if chr.is_whitespace() {
+                self.next_char();
+                continue;
+            }
+
+            let kind = match chr {
+                '[' => {
+                    self.next_char();
+                    TokenKind::TagOpen
+                }
+                ']' => {
+                    self.next_char();
+                    TokenKind::TagClose
+                }
+                '(' => {
+                    self.next_char();
+                    TokenKind::VariationOpen
+                }
+                ')' => {
+                    self.next_char();
+                    TokenKind::VariationClose
+                }
+                '*' => {
+                    self.next_char();
+                    TokenKind::Termination("*".to_string())
+                }
+                '.' => {
+                    // Stray periods, e.g. "1 ... e5", carry no meaning
+                    self.next_char();
+                    continue;
+                }
+                '"' => self.read_string(line, column)?,
+                '{' => self.read_brace_comment(line, column)?,
+                ';' => {
+                    self.next_char();
+                    let start = self.pos;
+                    self.skip_line();
+                    TokenKind::Comment(self.input[start..self.pos].trim().to_string())
+                }
+                '-' if self.input[self.pos..].starts_with(NULL_MOVE) => {
+                    self.next_char();
+                    self.next_char();
+                    TokenKind::Symbol(NULL_MOVE.to_string())
+                }
+                '$' => self.read_nag(line, column)?,
+                '!' | '?' => self.read_suffix_annotation(line, column)?,
+                chr if chr.is_ascii_alphanumeric() => self.read_symbol(),
+                chr => {
+                    return Err(self.error(
+                        line,
+                        column,
+                        format!("Unexpected character '{}'", chr),
+                    ));
+                }
+            };
+            return Ok(Some(Token { kind, line, column }));
+        }
+    }
+
+    fn read_string(&mut self, line: usize, column: usize) -> Result<TokenKind, PgnParseError> {
+        self.next_char();
+        let mut value = String::new();
+        loop {
+            match self.next_char() {
+                Some('"') => return Ok(TokenKind::Str(value)),
+                Some('\\') => match self.next_char() {
+                    Some(escaped @ ('"' | '\\')) => value.push(escaped),
+                    Some(other) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => break,
+                },
+                Some('\n') | None => break,
+                Some(chr) => value.push(chr),
+            }
+        }
+        Err(self.error(line, column, "Unterminated string".to_string()))
+    }
+
+    fn read_brace_comment(
+        &mut self,
+        line: usize,
+        column: usize,
+    ) -> Result<TokenKind, PgnParseError> {
+        self.next_char();
+        let start = self.pos;
+        loop {
+            match self.next_char() {
+                Some('}') => {
+                    let text = &self.input[start..self.pos - 1];
+                    return Ok(TokenKind::Comment(
+                        text.split_whitespace().collect::<Vec<_>>().join(" "),
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    return Err(self.error(line, column, "Unterminated comment".to_string()));
+                }
+            }
+        }
+    }
+
+    fn read_nag(&mut self, line: usize, column: usize) -> Result<TokenKind, PgnParseError> {
+        self.next_char();
+        let start = self.pos;
+        while self.peek_char().is_some_and(|chr| chr.is_ascii_digit()) {
+            self.next_char();
+        }
+        match self.input[start..self.pos].parse::<u8>() {
+            Ok(nag) => Ok(TokenKind::Nag(nag)),
+            Err(_) => Err(self.error(line, column, "Invalid NAG".to_string())),
+        }
+    }
+
+    fn read_suffix_annotation(
+        &mut self,
+        line: usize,
+        column: usize,
+    ) -> Result<TokenKind, PgnParseError> {
+        let start = self.pos;
+        while matches!(self.peek_char(), Some('!' | '?')) {
+            self.next_char();
+        }
+        let nag = match &self.input[start..self.pos] {
+            "!" => 1,
+            "?" => 2,
+            "!!" => 3,
+            "??" => 4,
+            "!?" => 5,
+            "?!" => 6,
+            other => {
+                return Err(self.error(
+                    line,
+                    column,
+                    format!("Unknown move annotation '{}'", other),
+                ));
+            }
+        };
+        Ok(TokenKind::Nag(nag))
+    }
+
+    fn read_symbol(&mut self) -> TokenKind {
+        let start = self.pos;
+        while self
+            .peek_char()
+            .is_some_and(|chr| chr.is_ascii_alphanumeric() || "_+#=:-/".contains(chr))
+        {
+            self.next_char();
+        }
+        let symbol = &self.input[start..self.pos];
+
+        if GAME_TERMINATIONS.contains(&symbol) {
+            return TokenKind::Termination(symbol.to_string());
+        }
+        if symbol.chars().all(|chr| chr.is_ascii_digit()) {
+            while self.peek_char() == Some('.') {
+                self.next_char();
+            }
+            return TokenKind::MoveNumber;
+        }
+        // Some programs write castling with zeros
+        let symbol = match symbol {
+            "0-0" => "O-O",
+            "0-0-0" => "O-O-O",
+            other => other,
+        };
+        TokenKind::Symbol(symbol.to_string())
+    }
+}
+
+pub struct PgnParser<'a> {
+    lexer: Lexer<'a>,
+    peeked: Option<Token>,
+}
+
+impl<'a> PgnParser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        PgnParser {
+            lexer: Lexer::new(input),
+            peeked: None,
+        }
+    }
+
+    pub fn next_game(&mut self) -> Option<Result<PgnGame, PgnParseError>> {
+        match self.peek_token() {
+            Ok(None) => return None,
+            Ok(Some(_)) => {}
+            Err(err) => return Some(Err(self.recover(err))),
+        }
+        match self.parse_game() {
+            Ok(game) => Some(Ok(game)),
+            Err(err) => Some(Err(self.recover(err))),
+        }
+    }
+
+    fn recover(&mut self, err: PgnParseError) -> PgnParseError {
+        self.peeked = None;
+        self.lexer.skip_to_next_game();
+        err
+    }
+
+    fn peek_token(&mut self) -> Result<Option<&Token>, PgnParseError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnParseError> {
+        self.peek_token()?;
+        Ok(self.peeked.take())
+    }
+
+    fn parse_game(&mut self) -> Result<PgnGame, PgnParseError> {
+        let mut game = PgnGame::default();
+
+        while let Some(Token {
+            kind: TokenKind::TagOpen,
+            ..
+        }) = self.peek_token()?
+        {
+            self.next_token()?;
+            let (name, value) = self.parse_tag()?;
+            game.tags.push((name, value));
+        }
+
+        let mut pending_comments = Vec::new();
+        game.moves = self.parse_line(0, &mut pending_comments)?;
+        game.comments = pending_comments;
+
+        game.result = match self.peek_token()? {
+            Some(Token {
+                kind: TokenKind::Termination(result),
+                ..
+            }) => {
+                let result = result.clone();
+                self.next_token()?;
+                result
+            }
+            // Missing termination marker, fall back on the tag
+            _ => game.tag("Result").unwrap_or("*").to_string(),
+        };
+        Ok(game)
+    }
+
+    fn parse_tag(&mut self) -> Result<(String, String), PgnParseError> {
+        let name = match self.next_token()? {
+            Some(Token {
+                kind: TokenKind::Symbol(name),
+                ..
+            }) => name,
+            token => return Err(self.unexpected(token, "a tag name")),
+        };
+        let value = match self.next_token()? {
+            Some(Token {
+                kind: TokenKind::Str(value),
+                ..
+            }) => value,
+            token => return Err(self.unexpected(token, "a tag value")),
+        };
+        match self.next_token()? {
+            Some(Token {
+                kind: TokenKind::TagClose,
+                ..
+            }) => Ok((name, value)),
+            token => Err(self.unexpected(token, "']'")),
+        }
+    }
+
+    // Parse moves until the end of the (sub)line, leaving the closing token in place
+    fn parse_line(
+        &mut self,
+        depth: usize,
+        pending_comments: &mut Vec<String>,
+    ) -> Result<Vec<PgnNode>, PgnParseError> {
+        let mut line: Vec<PgnNode> = Vec::new();
+
+        loop {
+            let token = match self.peek_token()? {
+                Some(token) => token.clone(),
+                None if depth == 0 => break,
+                None => {
+                    return Err(self.lexer.error(
+                        self.lexer.line,
+                        self.lexer.column,
+                        "Unterminated variation".to_string(),
+                    ));
+                }
+            };
+
+            match token.kind {
+                TokenKind::Termination(_) | TokenKind::TagOpen if depth == 0 => break,
+                TokenKind::VariationClose if depth > 0 => break,
+                TokenKind::MoveNumber => {}
+                TokenKind::Symbol(san) => {
+                    line.push(PgnNode {
+                        san,
+                        comments_before: mem::take(pending_comments),
+                        ..Default::default()
+                    });
+                }
+                TokenKind::Comment(text) => match line.last_mut() {
+                    Some(node) => {
+                        let (text, clock) = extract_clock(&text);
+                        if clock.is_some() {
+                            node.clock = clock;
+                        }
+                        if !text.is_empty() {
+                            node.comments_after.push(text);
+                        }
+                    }
+                    None => pending_comments.push(text),
+                },
+                TokenKind::Nag(nag) => match line.last_mut() {
+                    Some(node) => node.nags.push(nag),
+                    None => return Err(self.unexpected(Some(token), "a move before the NAG")),
+                },
+                TokenKind::VariationOpen => {
+                    if line.is_empty() {
+                        return Err(self.unexpected(Some(token), "a move before the variation"));
+                    }
+                    self.next_token()?;
+                    let mut variation_comments = Vec::new();
+                    let variation = self.parse_line(depth + 1, &mut variation_comments)?;
+                    if variation.is_empty() {
+                        return Err(self.lexer.error(
+                            token.line,
+                            token.column,
+                            "Empty variation".to_string(),
+                        ));
+                    }
+                    if let Some(node) = line.last_mut() {
+                        node.variations.push(variation);
+                    }
+                }
+                _ => return Err(self.unexpected(Some(token), "a move")),
+            }
+            self.next_token()?;
+        }
+        Ok(line)
+    }
+
+    fn unexpected(&self, token: Option<Token>, expected: &str) -> PgnParseError {
+        match token {
+            Some(token) => self.lexer.error(
+                token.line,
+                token.column,
+                format!("Expected {}, found {}", expected, token.kind),
+            ),
+            None => self.lexer.error(
+                self.lexer.line,
+                self.lexer.column,
+                format!("Expected {}, found end of input", expected),
+            ),
+        }
+    }
+}
+
+impl Iterator for PgnParser<'_> {
+    type Item = Result<PgnGame, PgnParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_game()
+    }
+}
+
+// Split a [%clk h:mm:ss] annotation out of a comment
+fn extract_clock(comment: &str) -> (String, Option<Duration>) {
+    let start = match comment.find("[%clk ") {
+        Some(start) => start,
+        None => return (comment.to_string(), None),
+    };
+    let end = match comment[start..].find(']') {
+        Some(end) => start + end,
+        None => return (comment.to_string(), None),
+    };
+
+    let mut seconds = 0.;
+    for part in comment[start + 6..end].trim().split(':') {
+        match part.parse::<f64>() {
+            Ok(value) => seconds = seconds * 60. + value,
+            Err(_) => return (comment.to_string(), None),
+        }
+    }
+    let rest = format!("{} {}", &comment[..start], &comment[end + 1..]);
+    (
+        rest.split_whitespace().collect::<Vec<_>>().join(" "),
+        Some(Duration::from_secs_f64(seconds)),
+    )
+}
+
+pub fn parse_pgn(input: &str) -> Result<Vec<PgnGame>, PgnParseError> {
+    PgnParser::new(input).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn_write::write_pgn;
+
+    const ANNOTATED_GAME: &str = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[Date "2024.01.02"]
+[Round "1"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+{Before the game} 1. e4 {[%clk 0:03:00]} e5 2. Nf3!? $14 (2. f4 exf4 {gambit} (2...
+d5)) 2... Nc6 3. Bb5 a6 ; the Morphy defence
+4. Ba4 1-0
+"#;
+
+    fn parse_one(input: &str) -> PgnGame {
+        let games = parse_pgn(input).expect("Test PGN parses");
+        assert_eq!(games.len(), 1);
+        games.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn parses_tags_comments_nags_and_variations() {
+        let game = parse_one(ANNOTATED_GAME);
+        assert_eq!(game.tag("Event"), Some("Casual \"blitz\""));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(
+            game.main_line(),
+            ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4"]
+        );
+        assert_eq!(game.moves[0].comments_before, ["Before the game"]);
+        assert_eq!(game.moves[0].clock, Some(Duration::from_secs(180)));
+        assert_eq!(game.moves[2].nags, [5, 14]);
+
+        let variation = &game.moves[2].variations[0];
+        assert_eq!(variation[0].san, "f4");
+        assert_eq!(variation[1].comments_after, ["gambit"]);
+        assert_eq!(variation[1].variations[0][0].san, "d5");
+        assert_eq!(game.moves[5].comments_after, ["the Morphy defence"]);
+    }
+
+    #[test]
+    fn written_games_parse_back_the_same() {
+        let game = parse_one(ANNOTATED_GAME);
+        let written = write_pgn(&game);
+        assert_eq!(parse_one(&written), game);
+    }
+
+    #[test]
+    fn reads_castling_with_zeros_and_stray_periods() {
+        let game = parse_one("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 ... Nf6 *");
+        assert_eq!(game.moves[6].san, "O-O");
+        assert_eq!(game.moves[7].san, "Nf6");
+        assert_eq!(game.result, "*");
+    }
+
+    #[test]
+    fn reads_null_moves() {
+        let game = parse_one("1. e4 -- 2. d4 *");
+        assert_eq!(game.main_line(), ["e4", NULL_MOVE, "d4"]);
+        assert!(write_pgn(&game).contains("1. e4 -- 2. d4 *"));
+    }
+
+    #[test]
+    fn reports_line_and_column_of_errors() {
+        let err = parse_pgn("[Event \"?\"]\n\n1. e4 & e5 *").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 7));
+        assert_eq!(err.message, "Unexpected character '&'");
+
+        let err = parse_pgn("[Event \"unterminated]\n\n1. e4 *").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 8));
+        assert_eq!(err.message, "Unterminated string");
+
+        let err = parse_pgn("1. e4 (e5 *").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 11));
+
+        let err = parse_pgn("1. e4 $ *").unwrap_err();
+        assert_eq!(err.message, "Invalid NAG");
+    }
+
+    #[test]
+    fn skips_the_rest_of_a_game_after_an_error_in_its_tags() {
+        let input = "[Event \"broken\"]\n[Site & \"?\"]\n[Round \"1\"]\n\n1. e4 e5 1-0\n\n\
+                     [Event \"next\"]\n\n1. d4 d5 0-1\n";
+        let games: Vec<_> = PgnParser::new(input).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].as_ref().unwrap_err().line, 2);
+        let next = games[1]
+            .as_ref()
+            .expect("The game after the broken one parses");
+        assert_eq!(next.tag("Event"), Some("next"));
+        assert_eq!(next.main_line(), ["d4", "d5"]);
+    }
+
+    #[test]
+    fn skips_the_rest_of_a_game_after_an_error_in_its_moves() {
+        let input = "[Event \"broken\"]\n\n1. e4 & e5 2. Nf3 1-0\n\n[Event \"next\"]\n\n1. c4 *\n";
+        let games: Vec<_> = PgnParser::new(input).collect();
+        assert_eq!(games.len(), 2);
+        assert!(games[0].is_err());
+        assert_eq!(games[1].as_ref().unwrap().main_line(), ["c4"]);
+    }
+}
@@ -1,74 +1,430 @@
+use super::pgn_parse::{GAME_TERMINATIONS, PgnGame, PgnParseError, PgnParser, parse_pgn};
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
 use std::{
     fs::File,
-    io::{BufRead, BufReader, Error},
+    io::{self, BufRead, BufReader, Error, Read, Seek, SeekFrom},
+    path::Path,
 };
 
-pub fn read_games(
-    filepath: &str,
-    games_to_read: usize,
-) -> Result<Vec<(Vec<String>, String)>, Error> {
-    let file = File::open(filepath)?;
-    let reader = BufReader::new(file);
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
 
-    let mut games = Vec::new();
-    let mut curr_game = String::new();
-
-    let mut dead_space_cnt: u8 = 0;
-    let mut dead_space = false;
-
-    for line in reader.lines() {
-        let line = line?;
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            if dead_space {
-                continue;
-            } else {
-                dead_space = true;
-                dead_space_cnt += 1;
-            }
-        } else {
-            dead_space = false;
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    // Magic bytes win over the file extension, which is only a fallback
+    pub fn detect(filepath: &str, header: &[u8]) -> Compression {
+        if header.starts_with(GZIP_MAGIC) {
+            return Compression::Gzip;
+        }
+        if header.starts_with(ZSTD_MAGIC) {
+            return Compression::Zstd;
+        }
+        if header.starts_with(BZIP2_MAGIC) {
+            return Compression::Bzip2;
+        }
+        match Path::new(filepath).extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            Some("bz2") => Compression::Bzip2,
+            _ => Compression::None,
+        }
+    }
+}
+
+pub type PgnInput = Box<dyn BufRead + Send>;
+
+// Open a PGN file, transparently decompressing .gz, .zst and .bz2 archives
+pub fn open_pgn_file(filepath: &str) -> Result<(PgnInput, Compression), Error> {
+    let mut file = BufReader::new(File::open(filepath)?);
+    let compression = Compression::detect(filepath, file.fill_buf()?);
+    Ok((decompress(file, compression)?, compression))
+}
+
+fn decompress(file: BufReader<File>, compression: Compression) -> Result<PgnInput, Error> {
+    let input: PgnInput = match compression {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(file)?)),
+        Compression::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(file))),
+    };
+    Ok(input)
+}
+
+// The unparsed text of a single game and where it was found in the input
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawPgnGame {
+    pub offset: u64,
+    pub line: usize,
+    pub text: String,
+}
+
+impl RawPgnGame {
+    pub fn parse(&self) -> Result<PgnGame, PgnParseError> {
+        let mut parser = PgnParser::new(&self.text);
+        let result = match parser.next_game() {
+            Some(result) => result,
+            None => Err(PgnParseError {
+                line: 1,
+                column: 1,
+                message: "No game found".to_string(),
+            }),
+        };
+        // Report positions relative to the whole input rather than this game
+        result.map_err(|mut err| {
+            err.line += self.line - 1;
+            err
+        })
+    }
+}
+
+// Reads games one at a time so only the current game is ever held in memory
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    offset: u64,
+    line: usize,
+    // A line already read that belongs to the next game, and how many bytes it took up
+    pending_line: Option<(String, u64)>,
+}
+
+impl PgnReader<PgnInput> {
+    pub fn open(filepath: &str) -> Result<Self, Error> {
+        let (input, _) = open_pgn_file(filepath)?;
+        Ok(PgnReader::new(input))
+    }
+
+    // Resume reading at a game offset previously returned by the reader.
+    // Offsets count decompressed bytes, so compressed files are skipped through.
+    pub fn open_at(filepath: &str, offset: u64) -> Result<Self, Error> {
+        let mut file = BufReader::new(File::open(filepath)?);
+        let compression = Compression::detect(filepath, file.fill_buf()?);
+        if compression == Compression::None {
+            file.seek(SeekFrom::Start(offset))?;
+        }
+
+        let mut input = decompress(file, compression)?;
+        if compression != Compression::None {
+            io::copy(&mut input.by_ref().take(offset), &mut io::sink())?;
+        }
+        Ok(PgnReader::with_offset(input, offset))
+    }
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_offset(reader, 0)
+    }
+
+    // For readers that are already positioned at `offset`, line numbers restart at 1
+    pub fn with_offset(reader: R, offset: u64) -> Self {
+        PgnReader {
+            reader,
+            offset,
+            line: 1,
+            pending_line: None,
+        }
+    }
+
+    // Byte offset at which the next game starts
+    pub fn offset(&self) -> u64 {
+        match &self.pending_line {
+            Some((_, bytes)) => self.offset - bytes,
+            None => self.offset,
         }
-        if dead_space_cnt == 2 {
-            games.push(get_move_list(curr_game.clone().trim()));
-            curr_game.clear();
+    }
 
-            dead_space_cnt = 0;
-            if games_to_read != 0 && games.len() == games_to_read {
+    // Invalid UTF-8 is replaced rather than failing the whole file, but the offset still
+    // counts the bytes as they are in the input
+    fn read_line(&mut self) -> Result<Option<(String, u64)>, Error> {
+        if let Some(pending) = self.pending_line.take() {
+            return Ok(Some(pending));
+        }
+        let mut bytes = Vec::new();
+        let read = self.reader.read_until(b'\n', &mut bytes)? as u64;
+        if read == 0 {
+            return Ok(None);
+        }
+        self.offset += read;
+        Ok(Some((String::from_utf8_lossy(&bytes).into_owned(), read)))
+    }
+
+    fn unread_line(&mut self, line: String, bytes: u64) {
+        self.pending_line = Some((line, bytes));
+    }
+
+    pub fn next_raw_game(&mut self) -> Result<Option<RawPgnGame>, Error> {
+        let mut game = RawPgnGame {
+            offset: self.offset(),
+            line: self.line,
+            text: String::new(),
+        };
+        let mut seen_movetext = false;
+        let mut in_comment = false;
+        let mut terminated = false;
+
+        loop {
+            let line_start = self.offset();
+            let (line, bytes) = match self.read_line()? {
+                Some(line) => line,
+                None => break,
+            };
+            let trimmed = line.trim();
+
+            // A new tag section, or anything after the result, belongs to the next game
+            if !in_comment
+                && ((seen_movetext && trimmed.starts_with('['))
+                    || (terminated && !trimmed.is_empty()))
+            {
+                self.unread_line(line, bytes);
                 break;
             }
-            continue;
+
+            if game.text.trim().is_empty() {
+                game.offset = line_start;
+                game.line = self.line;
+            }
+            if in_comment || !(trimmed.is_empty() || trimmed.starts_with(['[', '%'])) {
+                seen_movetext = true;
+            }
+            in_comment = update_comment_state(in_comment, trimmed);
+            if seen_movetext && !in_comment && !trimmed.is_empty() {
+                terminated = ends_with_termination(trimmed);
+            }
+
+            self.line += 1;
+            game.text.push_str(&line);
         }
-        if dead_space_cnt == 1 {
-            curr_game.push_str(&line.trim());
-            curr_game.push(' ');
+
+        if game.text.trim().is_empty() {
+            return Ok(None);
         }
+        Ok(Some(game))
+    }
+
+    pub fn raw_games(self) -> RawPgnGames<R> {
+        RawPgnGames { reader: self }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_raw_game() {
+            Ok(Some(raw_game)) => Some(raw_game.parse().map_err(Error::from)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+pub struct RawPgnGames<R: BufRead> {
+    reader: PgnReader<R>,
+}
+
+impl<R: BufRead> Iterator for RawPgnGames<R> {
+    type Item = Result<RawPgnGame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_raw_game().transpose()
     }
+}
 
-    if games_to_read == 0 || games.len() < games_to_read {
-        if !curr_game.trim().is_empty() {
-            games.push(get_move_list(curr_game.trim()))
+// Whether we are still inside a {} comment at the end of this line
+fn update_comment_state(mut in_comment: bool, line: &str) -> bool {
+    for chr in line.chars() {
+        match chr {
+            '{' if !in_comment => in_comment = true,
+            '}' if in_comment => in_comment = false,
+            ';' if !in_comment => break,
+            _ => {}
         }
     }
+    in_comment
+}
+
+fn ends_with_termination(text: &str) -> bool {
+    text.split_whitespace()
+        .last()
+        .is_some_and(|token| GAME_TERMINATIONS.contains(&token))
+}
+
+pub fn read_games(
+    filepath: &str,
+    games_to_read: usize,
+) -> Result<Vec<(Vec<String>, String)>, Error> {
+    let mut games = Vec::new();
+    for game in PgnReader::open(filepath)? {
+        let game = game?;
+        games.push((game.main_line(), game.result));
 
+        if games_to_read != 0 && games.len() == games_to_read {
+            break;
+        }
+    }
     Ok(games)
 }
 
 pub fn get_move_list(game_str: &str) -> (Vec<String>, String) {
-    let mut moves = Vec::new();
+    match parse_pgn(game_str) {
+        Ok(games) if !games.is_empty() => (games[0].main_line(), games[0].result.clone()),
+        _ => (Vec::new(), String::new()),
+    }
+}
 
-    let segments: Vec<&str> = game_str.split('.').collect();
-    let mut result = String::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
 
-    for (i, segment) in segments.iter().skip(1).enumerate() {
-        let splt: Vec<&str> = segment.split_whitespace().collect();
-        for mv in &splt[..splt.len() - 1] {
-            moves.push(mv.to_string());
-            if i == segments.len() - 2 {
-                result = splt[splt.len() - 1].to_owned();
+    fn raw_games(input: &[u8]) -> Vec<RawPgnGame> {
+        PgnReader::new(Cursor::new(input.to_vec()))
+            .raw_games()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn splits_games_at_their_tag_sections() {
+        let input = b"[Event \"a\"]\n\n1. e4 e5 1-0\n\n[Event \"b\"]\n\n1. d4 *\n";
+        let games = raw_games(input);
+        assert_eq!(games.len(), 2);
+        assert_eq!((games[0].offset, games[0].line), (0, 1));
+        let second = input.windows(9).rposition(|w| w == b"[Event \"b").unwrap() as u64;
+        assert_eq!((games[1].offset, games[1].line), (second, 5));
+        assert!(games[1].text.starts_with("[Event \"b\"]"));
+        assert_eq!(games[1].parse().unwrap().main_line(), ["d4"]);
+    }
+
+    #[test]
+    fn offsets_count_bytes_of_invalid_utf8_and_crlf_lines() {
+        let mut input = b"[Event \"caf\xe9\"]\r\n\r\n1. e4 *\r\n\r\n".to_vec();
+        let second = input.len() as u64;
+        input.extend_from_slice(b"[Event \"b\"]\r\n\r\n1. d4 *\r\n");
+        let games = raw_games(&input);
+        assert_eq!(games.len(), 2);
+        assert!(games[0].text.contains("caf\u{fffd}"));
+        assert_eq!(games[1].offset, second);
+        assert_eq!(&input[second as usize..second as usize + 6], b"[Event");
+    }
+
+    const TWO_GAMES: &[u8] = b"[Event \"a\"]\n\n1. e4 *\n\n[Event \"b\"]\n\n1. d4 *\n";
+
+    fn compress(data: &[u8], compression: Compression) -> Vec<u8> {
+        match compression {
+            Compression::None => data.to_vec(),
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0).unwrap(),
+            Compression::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
             }
         }
     }
 
-    return (moves, result);
+    fn temp_file(name: &str, contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("pgn_read_{}_{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn reads_compressed_files_by_magic_bytes_or_extension() {
+        let cases = [
+            (Compression::Gzip, "games.gz"),
+            (Compression::Zstd, "games.zst"),
+            (Compression::Bzip2, "games.bz2"),
+            // A misleading extension doesn't matter when the magic bytes are there
+            (Compression::Gzip, "gzip.pgn"),
+            (Compression::Bzip2, "bzip2.zst"),
+        ];
+        for (compression, name) in cases {
+            let path = temp_file(name, &compress(TWO_GAMES, compression));
+            let (_, detected) = open_pgn_file(&path).unwrap();
+            assert_eq!(detected, compression, "{}", name);
+
+            let events: Vec<String> = PgnReader::open(&path)
+                .unwrap()
+                .map(|game| game.unwrap().tag("Event").unwrap().to_string())
+                .collect();
+            assert_eq!(events, ["a", "b"], "{}", name);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn reads_every_member_of_concatenated_archives() {
+        let (first, second) = TWO_GAMES.split_at(TWO_GAMES.len() / 2);
+        for (compression, name) in [
+            (Compression::Gzip, "multi.gz"),
+            (Compression::Bzip2, "multi.bz2"),
+            (Compression::Zstd, "multi.zst"),
+        ] {
+            let mut data = compress(first, compression);
+            data.extend(compress(second, compression));
+            let path = temp_file(name, &data);
+            assert_eq!(PgnReader::open(&path).unwrap().count(), 2, "{}", name);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn open_at_counts_decompressed_bytes() {
+        let second = TWO_GAMES
+            .windows(9)
+            .rposition(|w| w == b"[Event \"b")
+            .unwrap() as u64;
+        for (compression, name) in [
+            (Compression::Gzip, "at.gz"),
+            (Compression::Zstd, "at.zst"),
+            (Compression::Bzip2, "at.bz2"),
+        ] {
+            let path = temp_file(name, &compress(TWO_GAMES, compression));
+            let offsets: Vec<u64> = PgnReader::open(&path)
+                .unwrap()
+                .raw_games()
+                .map(|game| game.unwrap().offset)
+                .collect();
+            assert_eq!(offsets, [0, second], "{}", name);
+            let game = PgnReader::open_at(&path, second)
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(game.tag("Event"), Some("b"), "{}", name);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn open_at_resumes_from_a_game_offset() {
+        let path = temp_file("open_at.pgn", TWO_GAMES);
+        let offsets: Vec<u64> = PgnReader::open(&path)
+            .unwrap()
+            .raw_games()
+            .map(|game| game.unwrap().offset)
+            .collect();
+        let game = PgnReader::open_at(&path, offsets[1])
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(game.tag("Event"), Some("b"));
+        std::fs::remove_file(path).unwrap();
+    }
 }
@@ -0,0 +1,279 @@
+use super::game::{Color, Game};
+use super::pgn_parse::{NULL_MOVE, PgnNode, PgnParseError, SEVEN_TAG_ROSTER};
+use super::pgn_read::{PgnReader, RawPgnGame};
+use rayon::prelude::*;
+use std::{
+    fmt,
+    io::{Error, Write},
+};
+
+// Games are validated in parallel a batch at a time, which keeps memory bounded and output ordered
+const BATCH_SIZE: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    Parse(PgnParseError),
+    InvalidSetup(String),
+    IllegalMove {
+        move_number: String,
+        san: String,
+        fen: String,
+    },
+    // A "--" pass, which the engine can't play
+    NullMove {
+        move_number: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Parse(err) => write!(f, "Unparseable PGN at {}", err),
+            ValidationError::InvalidSetup(message) => write!(f, "Invalid FEN tag: {}", message),
+            ValidationError::IllegalMove {
+                move_number,
+                san,
+                fen,
+            } => write!(
+                f,
+                "Illegal move {} {} in position {}",
+                move_number, san, fen
+            ),
+            ValidationError::NullMove { move_number } => {
+                write!(f, "Null move at {} isn't supported", move_number)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameReport {
+    // 1-based position of the game in the file
+    pub index: usize,
+    pub line: usize,
+    pub tags: Vec<(String, String)>,
+    pub errors: Vec<ValidationError>,
+}
+
+impl GameReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for GameReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Game {} (line {})", self.index, self.line)?;
+        for (name, value) in &self.tags {
+            if SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                write!(f, " [{} \"{}\"]", name, value)?;
+            }
+        }
+        for err in &self.errors {
+            write!(f, "\n    {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationSummary {
+    pub games: usize,
+    pub valid_games: usize,
+    pub unparseable_games: usize,
+    pub invalid_setups: usize,
+    pub illegal_moves: usize,
+}
+
+impl ValidationSummary {
+    fn add(&mut self, report: &GameReport) {
+        self.games += 1;
+        if report.is_valid() {
+            self.valid_games += 1;
+        }
+        for err in &report.errors {
+            match err {
+                ValidationError::Parse(_) => self.unparseable_games += 1,
+                ValidationError::InvalidSetup(_) => self.invalid_setups += 1,
+                ValidationError::IllegalMove { .. } | ValidationError::NullMove { .. } => {
+                    self.illegal_moves += 1
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValidationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} games, {} valid, {} invalid ({} unparseable, {} bad setups, {} illegal moves)",
+            self.games,
+            self.valid_games,
+            self.games - self.valid_games,
+            self.unparseable_games,
+            self.invalid_setups,
+            self.illegal_moves
+        )
+    }
+}
+
+pub fn validate_game(index: usize, raw_game: &RawPgnGame) -> GameReport {
+    let mut report = GameReport {
+        index,
+        line: raw_game.line,
+        tags: Vec::new(),
+        errors: Vec::new(),
+    };
+    let game = match raw_game.parse() {
+        Ok(game) => game,
+        Err(err) => {
+            report.errors.push(ValidationError::Parse(err));
+            return report;
+        }
+    };
+    report.tags = game.tags.clone();
+
+    match game.starting_position() {
+        Ok(start) => validate_line(&start, &game.moves, &mut report.errors),
+        Err(err) => report
+            .errors
+            .push(ValidationError::InvalidSetup(err.to_string())),
+    }
+    report
+}
+
+// Play through a line, checking its variations from the position they branch off at
+fn validate_line(start: &Game, nodes: &[PgnNode], errors: &mut Vec<ValidationError>) {
+    let mut position = start.clone();
+    for node in nodes {
+        for variation in &node.variations {
+            validate_line(&position, variation, errors);
+        }
+
+        if node.san == NULL_MOVE {
+            errors.push(ValidationError::NullMove {
+                move_number: position.move_number_str(),
+            });
+            return;
+        }
+        match position.move_from_san(&node.san) {
+            Some(mv) => position.make_move(mv),
+            None => {
+                errors.push(ValidationError::IllegalMove {
+                    move_number: position.move_number_str(),
+                    san: node.san.clone(),
+                    fen: position.to_full_fen_str(),
+                });
+                // The rest of the line can't be followed without this move
+                return;
+            }
+        }
+    }
+}
+
+impl Game {
+    // "12." before a White move and "12..." before a Black one
+    pub fn move_number_str(&self) -> String {
+        let fen = self.to_full_fen_str();
+        let fullmove = fen.split_whitespace().last().unwrap_or("1");
+        match self.next_player {
+            Color::White => format!("{}.", fullmove),
+            Color::Black => format!("{}...", fullmove),
+        }
+    }
+}
+
+// Validate every game in a file, passing each report to `on_report` in file order.
+// Valid games are copied unchanged to `clean_output` when given.
+pub fn validate_file(
+    filepath: &str,
+    games_to_read: usize,
+    mut on_report: impl FnMut(&GameReport),
+    mut clean_output: Option<&mut dyn Write>,
+) -> Result<ValidationSummary, Error> {
+    let games_to_read = if games_to_read == 0 {
+        usize::MAX
+    } else {
+        games_to_read
+    };
+    let mut raw_games = PgnReader::open(filepath)?.raw_games().take(games_to_read);
+    let mut summary = ValidationSummary::default();
+
+    loop {
+        let batch = raw_games
+            .by_ref()
+            .take(BATCH_SIZE)
+            .collect::<Result<Vec<RawPgnGame>, Error>>()?;
+        if batch.is_empty() {
+            break;
+        }
+
+        let first_index = summary.games + 1;
+        let reports: Vec<GameReport> = batch
+            .par_iter()
+            .enumerate()
+            .map(|(i, raw_game)| validate_game(first_index + i, raw_game))
+            .collect();
+
+        for (raw_game, report) in batch.iter().zip(&reports) {
+            summary.add(report);
+            on_report(report);
+            if report.is_valid()
+                && let Some(output) = clean_output.as_mut()
+            {
+                output.write_all(raw_game.text.trim_end().as_bytes())?;
+                output.write_all(b"\n\n")?;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(text: &str) -> GameReport {
+        let raw_game = RawPgnGame {
+            offset: 0,
+            line: 1,
+            text: text.to_string(),
+        };
+        validate_game(1, &raw_game)
+    }
+
+    #[test]
+    fn accepts_legal_games_and_variations() {
+        let report = report("[Event \"?\"]\n\n1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 *");
+        assert!(report.is_valid(), "{}", report);
+    }
+
+    #[test]
+    fn reports_illegal_moves_with_their_position() {
+        let report = report("1. e4 e5 2. Ke3 *");
+        assert_eq!(
+            report.errors,
+            [ValidationError::IllegalMove {
+                move_number: "2.".to_string(),
+                san: "Ke3".to_string(),
+                fen: "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_null_moves_as_unsupported() {
+        let report = report("1. e4 -- 2. d4 *");
+        assert_eq!(
+            report.errors,
+            [ValidationError::NullMove {
+                move_number: "1...".to_string()
+            }]
+        );
+        assert_eq!(
+            report.errors[0].to_string(),
+            "Null move at 1... isn't supported"
+        );
+    }
+}
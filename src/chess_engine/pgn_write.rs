@@ -0,0 +1,336 @@
+use super::game::{Color, Game, GameState};
+use super::pgn_parse::{PgnGame, PgnNode, SEVEN_TAG_ROSTER};
+use super::variant::is_standard;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Export format lines must fit in 80 columns
+const MAX_LINE_LENGTH: usize = 79;
+
+impl Game {
+    pub fn result_str(&self) -> &'static str {
+        match self.state {
+            GameState::Checkmate(Color::White) => "0-1",
+            GameState::Checkmate(Color::Black) => "1-0",
+            GameState::Stalemate | GameState::VariantDraw => "1/2-1/2",
+            GameState::VariantWin(Color::White) => "1-0",
+            GameState::VariantWin(Color::Black) => "0-1",
+            _ => "*",
+        }
+    }
+
+    pub fn to_pgn_game(&self) -> PgnGame {
+        // Walk back to the starting position, then replay to get the SAN of every move
+        let mut replay = self.clone();
+        while replay.undo_move().is_some() {}
+
+        let mut game = PgnGame {
+            result: self.result_str().to_string(),
+            ..Default::default()
+        };
+        game.set_tag("Date", &today_pgn_date());
+        game.set_tag("Result", self.result_str());
+
+        if self.chess960 && is_standard(self.variant) {
+            game.set_tag("Variant", "Chess960");
+        } else if !is_standard(self.variant) {
+            game.set_tag("Variant", self.variant.name());
+        }
+        let start = Game::from_variant(self.variant);
+        if replay.board != start.board
+            || replay.next_player != start.next_player
+            || replay.start_checks != start.start_checks
+            || replay.start_en_passant.is_some()
+            || replay.start_move_counters != start.start_move_counters
+            || replay.pockets != start.pockets
+        {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &replay.to_full_fen_str());
+        }
+
+        for &mv in &self.move_history {
+            let san = replay
+                .move_to_san(mv)
+                .expect("Move in history is not legal in its position");
+            game.moves.push(PgnNode {
+                san,
+                ..Default::default()
+            });
+            replay.redo_move();
+        }
+
+        if let Some(opening) = self.eco_opening() {
+            game.set_tag("ECO", &opening.eco);
+            game.set_tag("Opening", &opening.name);
+        }
+        game
+    }
+
+    pub fn to_pgn(&self) -> String {
+        write_pgn(&self.to_pgn_game())
+    }
+}
+
+pub fn write_pgn(game: &PgnGame) -> String {
+    let mut output = String::new();
+
+    // Seven Tag Roster comes first and in order, the rest alphabetically
+    for name in SEVEN_TAG_ROSTER {
+        let value = match (name, game.tag(name)) {
+            (_, Some(value)) => value,
+            ("Date", None) => "????.??.??",
+            ("Result", None) => game.result.as_str(),
+            (_, None) => "?",
+        };
+        output.push_str(&format_tag(name, value));
+    }
+    let mut other_tags: Vec<&(String, String)> = game
+        .tags
+        .iter()
+        .filter(|(name, _)| !SEVEN_TAG_ROSTER.contains(&name.as_str()))
+        .collect();
+    other_tags.sort();
+    for (name, value) in other_tags {
+        output.push_str(&format_tag(name, value));
+    }
+    output.push('\n');
+
+    let mut tokens = Vec::new();
+    for comment in &game.comments {
+        push_comment(&mut tokens, comment);
+    }
+    write_line(&game.moves, starting_ply(game), true, &mut tokens);
+    tokens.push(game.result.clone());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            output.push_str(&line);
+            output.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    output.push_str(&line);
+    output.push_str("\n\n");
+    output
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+    format!(
+        "[{} \"{}\"]\n",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+// Ply of the first move, counting from White's first move of the game
+fn starting_ply(game: &PgnGame) -> usize {
+    let fen = match game.tag("FEN") {
+        Some(fen) => fen,
+        None => return 0,
+    };
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let black_to_move = fields.get(1) == Some(&"b");
+    let fullmove = fields
+        .get(5)
+        .and_then(|number| number.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    (fullmove - 1) * 2 + black_to_move as usize
+}
+
+fn write_line(nodes: &[PgnNode], mut ply: usize, mut show_number: bool, tokens: &mut Vec<String>) {
+    for node in nodes {
+        for comment in &node.comments_before {
+            push_comment(tokens, comment);
+            show_number = true;
+        }
+
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{}.", ply / 2 + 1));
+        } else if show_number {
+            tokens.push(format!("{}...", ply / 2 + 1));
+        }
+        tokens.push(node.san.clone());
+        show_number = false;
+
+        for nag in &node.nags {
+            tokens.push(format!("${}", nag));
+        }
+
+        let mut comments = node.comments_after.clone();
+        if let Some(clock) = node.clock {
+            comments.push(format_clock(clock));
+        }
+        if !comments.is_empty() {
+            push_comment(tokens, &comments.join(" "));
+            show_number = true;
+        }
+
+        for variation in &node.variations {
+            let mut variation_tokens = Vec::new();
+            write_line(variation, ply, true, &mut variation_tokens);
+            // A variation always starts with a move number, so it has at least two tokens
+            if let Some(first) = variation_tokens.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation_tokens.last_mut() {
+                last.push(')');
+            }
+            tokens.append(&mut variation_tokens);
+            show_number = true;
+        }
+        ply += 1;
+    }
+}
+
+// Comments are split on whitespace so they can be wrapped like any other token
+fn push_comment(tokens: &mut Vec<String>, comment: &str) {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    if words.is_empty() {
+        tokens.push("{}".to_string());
+        return;
+    }
+    for (i, word) in words.iter().enumerate() {
+        let mut token = word.to_string();
+        if i == 0 {
+            token.insert(0, '{');
+        }
+        if i == words.len() - 1 {
+            token.push('}');
+        }
+        tokens.push(token);
+    }
+}
+
+fn format_clock(clock: Duration) -> String {
+    let seconds = clock.as_secs();
+    format!(
+        "[%clk {}:{:02}:{:02}]",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn today_pgn_date() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+
+    // Days since the epoch to a civil date (proleptic Gregorian calendar)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn_parse::parse_pgn;
+
+    fn play(game: &mut Game, sans: &str) {
+        for san in sans.split_whitespace() {
+            let mv = game.move_from_san(san).expect("Test move is legal");
+            game.make_move(mv);
+        }
+    }
+
+    #[test]
+    fn wraps_movetext_at_80_columns_without_splitting_tokens() {
+        let mut game = Game::from_standard_board();
+        play(
+            &mut game,
+            "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Nb8 d4 Nbd7 \
+             c4 c6 cxb5 axb5 Nc3 Bb7 Bg5 b4 Nb1 h6 Bh4 c5 dxe5 Nxe4 Bxe7 Qxe7 exd6 Qf6",
+        );
+        let pgn = game.to_pgn();
+        let movetext: Vec<&str> = pgn.split("\n\n").nth(1).unwrap().lines().collect();
+
+        assert!(movetext.len() > 1);
+        assert!(movetext.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        // Each line is as full as the next token allows
+        for pair in movetext.windows(2) {
+            let next_token = pair[1].split(' ').next().unwrap();
+            assert!(pair[0].len() + 1 + next_token.len() > MAX_LINE_LENGTH);
+        }
+        let parsed = parse_pgn(&pgn).unwrap().remove(0);
+        assert_eq!(parsed.moves.len(), game.move_history.len());
+    }
+
+    #[test]
+    fn result_tag_and_termination_follow_the_game_state() {
+        let cases = [
+            ("f3 e5 g4 Qh4#", "0-1"),
+            ("e4 e5 Bc4 Nc6 Qh5 Nf6 Qxf7#", "1-0"),
+            ("e4 e5", "*"),
+        ];
+        for (moves, result) in cases {
+            let mut game = Game::from_standard_board();
+            play(&mut game, moves);
+            let pgn = game.to_pgn();
+            assert!(pgn.contains(&format!("[Result \"{}\"]", result)), "{}", pgn);
+            assert!(pgn.trim_end().ends_with(&format!(" {}", result)), "{}", pgn);
+        }
+
+        // The black king on a8 has nowhere to go once White passes
+        let mut stalemate = Game::from_fen_str("k7/8/1Q6/8/8/8/8/7K w - - 0 1").unwrap();
+        play(&mut stalemate, "Kg2");
+        assert!(stalemate.to_pgn().trim_end().ends_with("1. Kg2 1/2-1/2"));
+    }
+
+    #[test]
+    fn games_from_a_position_get_setup_and_fen_tags() {
+        let fen = "k7/8/1Q6/8/8/8/8/7K w - - 0 40";
+        let mut game = Game::from_fen_str(fen).unwrap();
+        play(&mut game, "Qb5 Ka7");
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]"));
+        assert!(pgn.contains(&format!("[FEN \"{}\"]", fen)));
+        assert!(pgn.contains("40. Qb5 Ka7 *"));
+    }
+
+    #[test]
+    fn seven_tag_roster_comes_first_and_the_rest_sorted() {
+        let mut game = PgnGame {
+            result: "1/2-1/2".to_string(),
+            ..Default::default()
+        };
+        game.set_tag("Opening", "x");
+        game.set_tag("White", "A");
+        game.set_tag("Annotator", "y");
+        let names: Vec<String> = write_pgn(&game)
+            .lines()
+            .take_while(|line| line.starts_with('['))
+            .map(|line| line[1..].split(' ').next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Event",
+                "Site",
+                "Date",
+                "Round",
+                "White",
+                "Black",
+                "Result",
+                "Annotator",
+                "Opening"
+            ]
+        );
+        assert!(write_pgn(&game).contains("[Result \"1/2-1/2\"]"));
+    }
+}
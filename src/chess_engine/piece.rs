@@ -8,6 +8,111 @@ pub enum PieceType {
     Knight,
     Queen,
     King,
+    // A piece a variant defines, by its uppercase letter
+    Fairy(char),
+}
+
+// How a piece moves, as an offset that is mirrored and rotated into every direction.
+// A leaper jumps straight to the square, a rider keeps going that way until it is blocked.
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
+pub enum Movement {
+    Leaper(i32, i32),
+    Rider(i32, i32),
+}
+
+pub const KNIGHT: Movement = Movement::Leaper(1, 2);
+pub const BISHOP: Movement = Movement::Rider(1, 1);
+pub const ROOK: Movement = Movement::Rider(1, 0);
+
+impl PieceType {
+    // Pawns move differently depending on their colour and whether they capture,
+    // so they are generated separately, and kings castle on top of these. Fairy
+    // pieces move as their variant's definition says, see Game::movements.
+    pub fn movements(&self) -> &'static [Movement] {
+        match self {
+            PieceType::Pawn | PieceType::Fairy(_) => &[],
+            PieceType::Rook => &[ROOK],
+            PieceType::Bishop => &[BISHOP],
+            PieceType::Knight => &[KNIGHT],
+            PieceType::Queen => &[BISHOP, ROOK],
+            PieceType::King => &[Movement::Leaper(1, 1), Movement::Leaper(1, 0)],
+        }
+    }
+}
+
+// A piece a variant adds to the standard ones. Its letter is how FEN and SAN write it,
+// uppercase, and its name is what piece set images are called.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PieceDefinition {
+    pub name: &'static str,
+    pub letter: char,
+    pub movements: Vec<Movement>,
+    // Centipawns, for the search
+    pub value: i32,
+}
+
+impl PieceDefinition {
+    pub fn piece_type(&self) -> PieceType {
+        PieceType::Fairy(self.letter)
+    }
+}
+
+pub fn archbishop() -> PieceDefinition {
+    PieceDefinition {
+        name: "Archbishop",
+        letter: 'A',
+        movements: vec![BISHOP, KNIGHT],
+        value: 850,
+    }
+}
+
+pub fn chancellor() -> PieceDefinition {
+    PieceDefinition {
+        name: "Chancellor",
+        letter: 'C',
+        movements: vec![ROOK, KNIGHT],
+        value: 900,
+    }
+}
+
+pub fn amazon() -> PieceDefinition {
+    PieceDefinition {
+        name: "Amazon",
+        letter: 'Z',
+        movements: vec![BISHOP, ROOK, KNIGHT],
+        value: 1200,
+    }
+}
+
+// Jumps one square one way and three the other
+pub fn camel() -> PieceDefinition {
+    PieceDefinition {
+        name: "Camel",
+        letter: 'L',
+        movements: vec![Movement::Leaper(1, 3)],
+        value: 250,
+    }
+}
+
+impl Movement {
+    // Every distinct direction the offset points in
+    pub fn directions(&self) -> impl Iterator<Item = (i32, i32)> {
+        let (Movement::Leaper(a, b) | Movement::Rider(a, b)) = *self;
+        let all = [
+            (a, b),
+            (-a, b),
+            (a, -b),
+            (-a, -b),
+            (b, a),
+            (-b, a),
+            (b, -a),
+            (-b, -a),
+        ];
+        all.into_iter()
+            .enumerate()
+            .filter(move |&(i, dir)| !all[..i].contains(&dir))
+            .map(|(_, dir)| dir)
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
@@ -15,6 +120,8 @@ pub struct Piece {
     pub color: Color,
     pub piece_type: PieceType,
     pub has_moved: bool,
+    // Promoted from a pawn, so it goes back to being a pawn when captured in Crazyhouse
+    pub promoted: bool,
 }
 
 impl Color {
@@ -26,3 +133,100 @@ impl Color {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::validate::FenValidation;
+    use crate::variant::Variant;
+    use std::sync::LazyLock;
+
+    // Pieces no variant plays with, to check a definition is all a new piece needs
+    static TEST_PIECES: LazyLock<[PieceDefinition; 2]> = LazyLock::new(|| [amazon(), camel()]);
+
+    #[derive(Debug)]
+    struct TestVariant;
+
+    impl Variant for TestVariant {
+        fn name(&self) -> &'static str {
+            "Test"
+        }
+
+        fn fairy_pieces(&self) -> &[PieceDefinition] {
+            &*TEST_PIECES
+        }
+    }
+
+    fn position(fen: &str) -> Game {
+        Game::from_fen_str_validated(fen, &TestVariant, FenValidation::Strict)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn fairy_pieces_move_as_their_definition_says() {
+        // Queen moves plus knight jumps from d4, with the kings out of the way
+        let game = position("k7/8/8/8/3Z4/8/8/7K w - - 0 1");
+        assert_eq!(game.moves_from_square((3, 3)).len(), 27 + 8);
+        for san in ["Zd8", "Zh8", "Ze6", "Ze7"] {
+            assert_eq!(game.move_from_san(san).is_some(), san != "Ze7", "{}", san);
+        }
+
+        // A camel jumps one square one way and three the other
+        let game = position("7k/8/8/8/3L4/8/8/K7 w - - 0 1");
+        let mut targets: Vec<(usize, usize)> = game
+            .moves_from_square((3, 3))
+            .iter()
+            .map(|mv| mv.get_to_position())
+            .collect();
+        targets.sort();
+        assert_eq!(
+            targets,
+            [
+                (0, 2),
+                (0, 4),
+                (2, 0),
+                (2, 6),
+                (4, 0),
+                (4, 6),
+                (6, 2),
+                (6, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn fairy_letters_need_a_variant_that_defines_them() {
+        let fen = "k7/8/8/3l4/3Z4/8/8/7K w - - 0 1";
+        assert_eq!(position(fen).to_full_fen_str(), fen);
+        assert!(Game::from_fen_str(fen).is_err());
+        assert!(
+            Game::from_fen_str_validated(
+                "k7/8/8/8/3X4/8/8/7K w - - 0 1",
+                &TestVariant,
+                FenValidation::Lenient
+            )
+            .is_err()
+        );
+        // Nor do they mean anything in SAN without one
+        let game = Game::from_fen_str("k7/8/8/8/8/8/8/7K w - - 0 1").unwrap();
+        assert_eq!(game.move_from_san("Za2"), None);
+    }
+
+    #[test]
+    fn fairy_pieces_hash_by_letter_and_colour() {
+        let hashes: Vec<u64> = [
+            "k7/8/8/8/3Z4/8/8/7K w - - 0 1",
+            "k7/8/8/8/3z4/8/8/7K w - - 0 1",
+            "k7/8/8/8/3L4/8/8/7K w - - 0 1",
+            "k7/8/8/8/3Q4/8/8/7K w - - 0 1",
+        ]
+        .iter()
+        .map(|fen| position(fen).position_hash())
+        .collect();
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(!hashes[..i].contains(hash), "{}", i);
+        }
+    }
+}
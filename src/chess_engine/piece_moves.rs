@@ -1,69 +1,54 @@
 use super::game::*;
 use super::moves::*;
-use crate::chess_engine::piece::{Piece, PieceType};
+use crate::chess_engine::piece::{Movement, Piece, PieceType};
 
 impl Game {
     pub fn get_piece_moves(&self, from_position: (usize, usize)) -> Vec<Move> {
         // Check if piece is present
         match self.board[from_position.0][from_position.1] {
             Some(piece) => match piece.piece_type {
-                PieceType::Knight => self.knight_legal_moves(from_position, piece),
-                PieceType::Bishop => self.bishop_legal_moves(from_position, piece),
-                PieceType::Rook => self.rook_legal_moves(from_position, piece),
-                PieceType::Queen => self.queen_legalmoves(from_position, piece),
-                PieceType::King => self.king_legal_moves(from_position, piece),
                 PieceType::Pawn => self.pawn_legal_moves(from_position, piece),
+                PieceType::King => {
+                    let mut king_moves = self.movement_moves(from_position, piece);
+                    king_moves.append(&mut self.king_castles_moves(piece));
+                    king_moves
+                }
+                _ => self.movement_moves(from_position, piece),
             },
             None => Vec::new(),
         }
     }
 
-    // Knight ez
-    fn knight_legal_moves(&self, from_position: (usize, usize), piece: Piece) -> Vec<Move> {
-        let moves = [
-            (-2, -1),
-            (-1, -2),
-            (2, -1),
-            (-1, 2),
-            (-2, 1),
-            (1, -2),
-            (2, 1),
-            (1, 2),
-        ];
-        let possible_moves: Vec<Move> = moves
-            .iter()
-            .filter_map(|(dx, dy)| {
-                let new_row = from_position.0 as i32 + dx;
-                let new_col = from_position.1 as i32 + dy;
-                if new_col < 0 || new_row < 0 {
-                    return None;
-                }
-                let (new_row, new_col) = (new_row as usize, new_col as usize);
-                let to_position = (new_row, new_col);
-                if valid_position(to_position) {
-                    let capture = match self.board[new_row][new_col] {
-                        Some(other_piece) => {
-                            if piece.color == other_piece.color {
-                                return None;
-                            } else {
-                                Some(other_piece)
-                            }
-                        }
-                        None => None,
-                    };
-                    let my_move = Move::Normal(NormalMove {
+    // Moves derived from the piece's leaper and rider movements
+    fn movement_moves(&self, from_position: (usize, usize), piece: Piece) -> Vec<Move> {
+        let mut possible_moves = Vec::new();
+        for movement in self.movements(piece.piece_type) {
+            let max_dist = match movement {
+                Movement::Leaper(..) => Some(1),
+                Movement::Rider(..) => None,
+            };
+            for dir in movement.directions() {
+                for (to_position, capture) in
+                    self.sliding_moves_in_dir(from_position, dir, max_dist)
+                {
+                    let my_move = NormalMove {
                         piece: piece,
                         from_position: from_position,
                         to_position,
                         capture: capture,
                         game_state: GameState::Normal,
-                    });
-                    Some(my_move)
-                } else {
-                    None
+                    };
+                    // Check if can capture something on the way
+                    if let Some(other_piece) = capture {
+                        if piece.color != other_piece.color {
+                            possible_moves.push(Move::Normal(my_move));
+                        }
+                        break;
+                    }
+                    possible_moves.push(Move::Normal(my_move));
                 }
-            })
-            .collect();
+            }
+        }
         possible_moves
     }
 
@@ -76,122 +61,48 @@ impl Game {
     ) -> impl Iterator<Item = ((usize, usize), Option<Piece>)> {
         let max_dist = match max_dist {
             Some(number) => number,
-            None => 7,
+            None => MAX_BOARD_SIZE as i32 - 1,
         };
-        (1..max_dist + 1).filter_map(move |i| {
+        (1..max_dist + 1).map_while(move |i| {
             let new_row = from_position.0 as i32 + dir.0 * i;
             let new_col = from_position.1 as i32 + dir.1 * i;
+            if new_row < 0 || new_col < 0 {
+                return None;
+            }
             let to_position = (new_row as usize, new_col as usize);
 
-            if valid_position(to_position) {
-                match self.board[new_row as usize][new_col as usize] {
-                    Some(other_piece) => Some((to_position, Some(other_piece))),
-                    None => Some((to_position, None)),
-                }
+            if self.valid_position(to_position) {
+                Some((to_position, self.board[to_position.0][to_position.1]))
             } else {
                 None
             }
         })
     }
 
-    fn bishop_legal_moves(&self, from_position: (usize, usize), piece: Piece) -> Vec<Move> {
-        let mut possible_moves: Vec<Move> = Vec::new();
-        let directions = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
-        for dir in directions {
-            for (to_position, capture) in self.sliding_moves_in_dir(from_position, dir, None) {
-                let my_move = NormalMove {
-                    piece: piece,
-                    from_position: from_position,
-                    to_position,
-                    capture: capture,
-                    game_state: GameState::Normal,
-                };
-                // Check if can capture something on the way
-                if let Some(other_piece) = capture {
-                    if piece.color != other_piece.color {
-                        possible_moves.push(Move::Normal(my_move));
-                        break;
-                    } else {
-                        break;
-                    }
-                }
-                possible_moves.push(Move::Normal(my_move));
-            }
-        }
-        possible_moves
-    }
-
-    fn rook_legal_moves(&self, from_position: (usize, usize), piece: Piece) -> Vec<Move> {
-        let mut possible_moves = Vec::new();
-        let directions = [(-1, 0), (0, -1), (1, 0), (0, 1)];
-        for dir in directions {
-            for (to_position, capture) in self.sliding_moves_in_dir(from_position, dir, None) {
-                let my_move = NormalMove {
-                    piece: piece,
-                    from_position: from_position,
-                    to_position,
-                    capture: capture,
-                    game_state: GameState::Normal,
-                };
-                // Check if can capture something on the way
-                if let Some(other_piece) = capture {
-                    if piece.color != other_piece.color {
-                        possible_moves.push(Move::Normal(my_move));
-                        break;
-                    } else {
-                        break;
-                    }
-                }
-                possible_moves.push(Move::Normal(my_move));
-            }
-        }
-        possible_moves
-    }
-
-    fn queen_legalmoves(&self, from_position: (usize, usize), piece: Piece) -> Vec<Move> {
-        let mut b_moves = self.bishop_legal_moves(from_position, piece);
-        b_moves.append(&mut self.rook_legal_moves(from_position, piece));
-        b_moves
-    }
-
-    fn king_legal_moves(&self, from_position: (usize, usize), piece: Piece) -> Vec<Move> {
-        let mut possible_moves = Vec::new();
-        let directions = [
-            (-1, 0),
-            (0, -1),
-            (1, 0),
-            (0, 1),
-            (-1, -1),
-            (-1, 1),
-            (1, -1),
-            (1, 1),
-        ];
-        for dir in directions {
-            for (to_position, capture) in self.sliding_moves_in_dir(from_position, dir, Some(1)) {
-                let my_move = NormalMove {
-                    piece: piece,
-                    from_position: from_position,
-                    to_position,
-                    capture: capture,
-                    game_state: GameState::Normal,
-                };
-                // Check if can capture something on the way
-                if let Some(other_piece) = capture {
-                    if piece.color != other_piece.color {
-                        possible_moves.push(Move::Normal(my_move));
-                        break;
-                    } else {
-                        break;
-                    }
-                }
-                possible_moves.push(Move::Normal(my_move));
-            }
+    // Columns of the rooks the king can still castle with. Outside Chess960 the king has to
+    // be on e1/e8 and the rooks in the corners.
+    pub(crate) fn castling_rook_cols(&self, color: Color) -> Vec<usize> {
+        let row = self.home_row(color);
+        let king_col = match (0..self.cols).find(|&col| {
+            self.board[row][col].is_some_and(|p| {
+                p.piece_type == PieceType::King && p.color == color && !p.has_moved
+            })
+        }) {
+            Some(col) => col,
+            None => return Vec::new(),
+        };
+        if !self.chess960 && king_col != self.cols / 2 {
+            return Vec::new();
         }
 
-        // Add castles moves
-        let mut castles_moves = self.king_castles_moves(piece);
-        possible_moves.append(&mut castles_moves);
-        possible_moves
+        (0..self.cols)
+            .filter(|&col| {
+                (self.chess960 || col == 0 || col == self.cols - 1)
+                    && self.board[row][col].is_some_and(|p| {
+                        p.piece_type == PieceType::Rook && p.color == color && !p.has_moved
+                    })
+            })
+            .collect()
     }
 
     fn king_castles_moves(&self, piece: Piece) -> Vec<Move> {
@@ -199,39 +110,38 @@ impl Game {
         if piece.has_moved {
             return castle_moves;
         }
-        let row = match piece.color {
-            Color::White => 0,
-            Color::Black => 7,
+        let row = self.home_row(piece.color);
+        let king_col = match (0..self.cols).find(|&col| self.board[row][col] == Some(piece)) {
+            Some(col) => col,
+            None => return castle_moves,
         };
-        // Check kingside castle
-        if self.board[row][5] == None && self.board[row][6] == None {
-            if let Some(rook_piece) = self.board[row][7] {
-                if !rook_piece.has_moved
-                    && rook_piece.piece_type == PieceType::Rook
-                    && rook_piece.color == piece.color
-                {
-                    castle_moves.push(Move::Castles(CastlesMove {
-                        color: piece.color,
-                        side: CastleSide::King,
-                        game_state: GameState::Normal,
-                    }));
-                }
-            }
-        }
 
-        // Check queenside castle
-        if self.board[row][1] == None && self.board[row][2] == None && self.board[row][3] == None {
-            if let Some(rook_piece) = self.board[row][0] {
-                if !rook_piece.has_moved
-                    && rook_piece.piece_type == PieceType::Rook
-                    && rook_piece.color == piece.color
-                {
-                    castle_moves.push(Move::Castles(CastlesMove {
-                        color: piece.color,
-                        side: CastleSide::Queen,
-                        game_state: GameState::Normal,
-                    }));
-                }
+        for rook_col in self.castling_rook_cols(piece.color) {
+            let side = if rook_col > king_col {
+                CastleSide::King
+            } else {
+                CastleSide::Queen
+            };
+            let (king_to, rook_to) = self.castles_destination_cols(side);
+
+            // Everything the king and rook pass over has to be empty, apart from themselves
+            let path_clear = [(king_col, king_to), (rook_col, rook_to)]
+                .iter()
+                .flat_map(|&(from, to)| from.min(to)..=from.max(to))
+                .all(|col| col == king_col || col == rook_col || self.board[row][col].is_none());
+            if path_clear {
+                castle_moves.push(Move::Castles(CastlesMove {
+                    color: piece.color,
+                    side,
+                    row,
+                    king_col,
+                    rook_col,
+                    king_to,
+                    rook_to,
+                    classical: king_col == self.cols / 2
+                        && (rook_col == 0 || rook_col == self.cols - 1),
+                    game_state: GameState::Normal,
+                }));
             }
         }
         castle_moves
@@ -252,7 +162,7 @@ impl Game {
             if capture.is_some() {
                 break;
             }
-            add_pawn_move(
+            self.add_pawn_move(
                 &mut possible_moves,
                 from_position,
                 to_position,
@@ -266,7 +176,7 @@ impl Game {
             for (to_position, capture) in self.sliding_moves_in_dir(from_position, dir, Some(1)) {
                 if let Some(other_piece) = capture {
                     if other_piece.color != piece.color {
-                        add_pawn_move(
+                        self.add_pawn_move(
                             &mut possible_moves,
                             from_position,
                             to_position,
@@ -279,124 +189,102 @@ impl Game {
         }
 
         // En Passant moves
-        if let Some(last_move) = self.move_history.last() {
-            match *last_move {
-                Move::Normal(mv) => {
-                    let dist_moved = (mv.from_position.0 as i32 - mv.to_position.0 as i32).abs();
-                    if mv.piece.has_moved == false
-                        && dist_moved == 2
-                        && mv.piece.piece_type == PieceType::Pawn
-                    {
-                        if from_position.0 == mv.to_position.0 {
-                            let x_diff = from_position.1 as i32 - mv.to_position.1 as i32;
-                            if x_diff.abs() == 1 {
-                                possible_moves.push(Move::EnPassant(EnPassantMove {
-                                    from_position: from_position,
-                                    to_position: (
-                                        (from_position.0 as i32 + vertical_dir.0) as usize,
-                                        (from_position.1 as i32 - x_diff) as usize,
-                                    ),
-                                    pawn_capture_position: (mv.to_position),
-                                    game_state: GameState::Normal,
-                                }))
-                            }
-                        }
-                    }
+        if let Some(double_stepped) = self.double_stepped_pawn() {
+            if from_position.0 == double_stepped.0 {
+                let x_diff = from_position.1 as i32 - double_stepped.1 as i32;
+                if x_diff.abs() == 1 {
+                    possible_moves.push(Move::EnPassant(EnPassantMove {
+                        from_position: from_position,
+                        to_position: (
+                            (from_position.0 as i32 + vertical_dir.0) as usize,
+                            (from_position.1 as i32 - x_diff) as usize,
+                        ),
+                        pawn_capture_position: double_stepped,
+                        game_state: GameState::Normal,
+                    }))
                 }
-                _ => {}
-            };
+            }
         }
 
         possible_moves
     }
-}
 
-fn add_pawn_move(
-    possible_moves: &mut Vec<Move>,
-    from_position: (usize, usize),
-    to_position: (usize, usize),
-    piece: Piece,
-    capture: Option<Piece>,
-) {
-    if check_pawn_promotion(to_position, piece) {
-        possible_moves.extend_from_slice(&get_promotion_moves(
-            from_position,
-            to_position,
-            piece,
-            capture,
-        ));
-    } else {
-        let my_move = NormalMove {
-            piece: piece,
-            from_position: from_position,
-            to_position,
-            capture: capture,
-            game_state: GameState::Normal,
-        };
-        possible_moves.push(Move::Normal(my_move));
+    // Where the pawn that just moved two squares from its pawn row stands. Horde pawns
+    // stepping two squares from the first rank can't be taken en passant. Before the
+    // first move that comes from the en passant square in the FEN.
+    fn double_stepped_pawn(&self) -> Option<(usize, usize)> {
+        match self.move_history.last() {
+            Some(Move::Normal(mv)) => {
+                let dist_moved = (mv.from_position.0 as i32 - mv.to_position.0 as i32).abs();
+                (mv.piece.has_moved == false
+                    && dist_moved == 2
+                    && mv.piece.piece_type == PieceType::Pawn
+                    && mv.from_position.0 == self.pawn_row(mv.piece.color))
+                .then_some(mv.to_position)
+            }
+            Some(_) => None,
+            None => {
+                let (row, col) = self.start_en_passant?;
+                // The pawn passed over the square, moving away from the side to move
+                let row = match self.next_player {
+                    Color::White => row.checked_sub(1)?,
+                    Color::Black => row + 1,
+                };
+                self.board
+                    .get(row)?
+                    .get(col)
+                    .copied()
+                    .flatten()
+                    .is_some_and(|piece| {
+                        piece.piece_type == PieceType::Pawn && piece.color != self.next_player
+                    })
+                    .then_some((row, col))
+            }
+        }
     }
-}
 
-fn check_pawn_promotion(to_position: (usize, usize), piece: Piece) -> bool {
-    assert_eq!(piece.piece_type, PieceType::Pawn);
-    (piece.color == Color::White && to_position.0 == 7)
-        || (piece.color == Color::Black && to_position.0 == 0)
-}
+    // King and rook columns after castling, the same in Chess960 as in standard chess.
+    // On wider boards the king still ends up next to the rook's corner.
+    fn castles_destination_cols(&self, side: CastleSide) -> (usize, usize) {
+        match side {
+            CastleSide::King => (self.cols - 2, self.cols - 3),
+            CastleSide::Queen => (2, 3),
+        }
+    }
 
-fn get_promotion_moves(
-    from_position: (usize, usize),
-    to_position: (usize, usize),
-    piece: Piece,
-    capture: Option<Piece>,
-) -> [Move; 4] {
-    return [
-        Move::Promotion(PromotionMove {
-            piece: piece,
-            from_position: from_position,
-            to_position: to_position,
-            new_piece: Piece {
-                color: piece.color,
-                piece_type: PieceType::Bishop,
-                has_moved: true,
-            },
-            capture: capture,
-            game_state: GameState::Normal,
-        }),
-        Move::Promotion(PromotionMove {
-            piece: piece,
-            from_position: from_position,
-            to_position: to_position,
-            new_piece: Piece {
-                color: piece.color,
-                piece_type: PieceType::Knight,
-                has_moved: true,
-            },
-            capture: capture,
-            game_state: GameState::Normal,
-        }),
-        Move::Promotion(PromotionMove {
-            piece: piece,
-            from_position: from_position,
-            to_position: to_position,
-            new_piece: Piece {
-                color: piece.color,
-                piece_type: PieceType::Rook,
-                has_moved: true,
-            },
-            capture: capture,
-            game_state: GameState::Normal,
-        }),
-        Move::Promotion(PromotionMove {
-            piece: piece,
-            from_position: from_position,
-            to_position: to_position,
-            new_piece: Piece {
-                color: piece.color,
-                piece_type: PieceType::Queen,
-                has_moved: true,
-            },
-            capture: capture,
-            game_state: GameState::Normal,
-        }),
-    ];
+    fn add_pawn_move(
+        &self,
+        possible_moves: &mut Vec<Move>,
+        from_position: (usize, usize),
+        to_position: (usize, usize),
+        piece: Piece,
+        capture: Option<Piece>,
+    ) {
+        let (promotions, may_stay_pawn) = self.variant.promotions(self, piece.color, to_position.0);
+        if may_stay_pawn {
+            let my_move = NormalMove {
+                piece: piece,
+                from_position: from_position,
+                to_position,
+                capture: capture,
+                game_state: GameState::Normal,
+            };
+            possible_moves.push(Move::Normal(my_move));
+        }
+        for piece_type in promotions {
+            possible_moves.push(Move::Promotion(PromotionMove {
+                piece: piece,
+                from_position: from_position,
+                to_position: to_position,
+                new_piece: Piece {
+                    color: piece.color,
+                    piece_type,
+                    has_moved: true,
+                    promoted: true,
+                },
+                capture: capture,
+                game_state: GameState::Normal,
+            }));
+        }
+    }
 }
@@ -0,0 +1,111 @@
+use super::game::{Color, Game, GameState};
+use super::moves::Move;
+use super::piece::PieceType;
+use super::variant::Variant;
+
+const GOAL_ROW: usize = 7;
+
+// Both sides race their king to the eighth rank, and no move may give check. If White gets
+// there first, Black still has one move to get there too and draw.
+#[derive(Debug)]
+pub struct RacingKings;
+
+impl Variant for RacingKings {
+    fn name(&self) -> &'static str {
+        "Racing Kings"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1"
+    }
+
+    fn is_legal(&self, after: &mut Game, mv: Move, color: Color) -> bool {
+        !after.king_attacked_after(mv, color) && !after.in_check(color.opposite())
+    }
+
+    fn decisive_state(&self, after: &Game, color: Color, _gives_check: bool) -> Option<GameState> {
+        let white_home = king_reached_goal(after, Color::White);
+        let black_home = king_reached_goal(after, Color::Black);
+        match color {
+            Color::Black if black_home && white_home => Some(GameState::VariantDraw),
+            Color::Black if black_home => Some(GameState::VariantWin(Color::Black)),
+            Color::Black if white_home => Some(GameState::VariantWin(Color::White)),
+            // Black is to move in `after`, so see if its king can still catch up
+            Color::White if white_home => {
+                let black_catches_up = after.get_all_legal_moves(false).iter().any(|mv| {
+                    matches!(mv, Move::Normal(mv)
+                        if mv.piece.piece_type == PieceType::King && mv.to_position.0 == GOAL_ROW)
+                });
+                (!black_catches_up).then_some(GameState::VariantWin(Color::White))
+            }
+            _ => None,
+        }
+    }
+
+    fn goal_squares(&self) -> Vec<(usize, usize)> {
+        (0..8).map(|col| (GOAL_ROW, col)).collect()
+    }
+}
+
+fn king_reached_goal(game: &Game, color: Color) -> bool {
+    game.board[GOAL_ROW]
+        .iter()
+        .any(|place| place.is_some_and(|p| p.piece_type == PieceType::King && p.color == color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FenValidation;
+
+    fn racing_kings(fen: &str) -> Game {
+        Game::from_fen_str_validated(fen, &RacingKings, FenValidation::Lenient)
+            .unwrap()
+            .0
+    }
+
+    fn after(game: &Game, san: &str) -> Game {
+        let mut game = game.clone();
+        game.make_move(game.move_from_san(san).expect("Test move is legal"));
+        game
+    }
+
+    #[test]
+    fn start_position_moves_never_give_check() {
+        let game = Game::from_variant(&RacingKings);
+        assert_eq!(game.next_legal_moves.len(), 21);
+
+        // The rook could reach the black king's file, but that would be check
+        let game = racing_kings("k7/8/8/8/8/8/8/1R5K w - - 0 1");
+        assert!(game.move_from_san("Ra1").is_none());
+        assert!(game.move_from_san("Rb2").is_some());
+    }
+
+    #[test]
+    fn first_king_to_the_eighth_rank_wins() {
+        let game = racing_kings("8/1K6/8/8/8/8/8/6k1 w - - 0 1");
+        assert_eq!(
+            after(&game, "Kb8").state,
+            GameState::VariantWin(Color::White)
+        );
+
+        let game = racing_kings("8/6k1/8/8/8/8/K7/8 b - - 0 1");
+        assert_eq!(
+            after(&game, "Kg8").state,
+            GameState::VariantWin(Color::Black)
+        );
+    }
+
+    #[test]
+    fn black_catching_up_draws() {
+        let game = racing_kings("8/1K4k1/8/8/8/8/8/8 w - - 0 1");
+        let game = after(&game, "Kb8");
+        assert_eq!(game.state, GameState::Normal);
+        assert_eq!(after(&game, "Kg8").state, GameState::VariantDraw);
+        // Not catching up loses
+        assert_eq!(
+            after(&game, "Kf7").state,
+            GameState::VariantWin(Color::White)
+        );
+    }
+}
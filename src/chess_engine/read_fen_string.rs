@@ -1,23 +1,69 @@
-use super::game::{Color, Game};
+use super::game::{Color, Game, MAX_BOARD_SIZE, board_position_to_notation};
+use super::moves::Move;
 use super::piece::{Piece, PieceType};
+use super::validate::{FenValidation, PositionError};
+use super::variant::{Standard, Variant};
 use std::io::{self, Error, ErrorKind};
+use std::iter::Peekable;
+use std::str::Chars;
 
 const FEN_LINE_SEPARATOR: char = '/';
-const ALLOWED_LOWERCASE_CHARS: [char; 6] = ['p', 'b', 'r', 'n', 'k', 'q'];
+// Smallest board castling still fits on
+const MIN_BOARD_SIZE: usize = 4;
 
 impl Game {
+    // Accepts either just the board or a full FEN, validated leniently: castling rights and
+    // en passant squares the position can't use are dropped, other problems are let through
     pub fn from_fen_str(fen: &str) -> Result<Game, io::Error> {
-        let mut game = Game::from_blank_board();
-        check_fen_str(fen)?;
+        Ok(Self::from_fen_str_validated(fen, &Standard, FenValidation::Lenient)?.0)
+    }
 
-        let mut row = 7;
+    // The position in `variant`, and the castling rights it had to drop for lack of a
+    // king or rook
+    pub(crate) fn read_fen(
+        fen: &str,
+        variant: &'static dyn Variant,
+    ) -> Result<(Game, Vec<PositionError>), io::Error> {
+        let mut fields = fen.split_whitespace();
+        let board = fields.next().unwrap_or("");
+        // Crazyhouse pockets follow the board in brackets, like [Qn]
+        let (board, pockets) = match board.split_once('[') {
+            Some((board, pockets)) => (board, pockets.trim_end_matches(']')),
+            None => (board, ""),
+        };
+        let (rows, cols) = check_fen_str(board)?;
+        let mut game = Game::from_blank_board_sized(rows, cols);
+        // Set first, as it decides the pawn rows and the fairy pieces there are
+        game.variant = variant;
+        for chr in pockets.chars() {
+            if !"pnbrq".contains(chr.to_ascii_lowercase()) {
+                return Err(Error::other(format!(
+                    "Pockets can only hold pawns, knights, bishops, rooks and queens, not {}",
+                    chr
+                )));
+            }
+            let piece = alg_to_piece(chr);
+            game.pocket_mut(piece.color).add(piece.piece_type);
+        }
+
+        let mut row = rows - 1;
         let mut col = 0;
 
-        for chr in fen.chars() {
+        let mut chars = board.chars().peekable();
+        while let Some(chr) = chars.next() {
             //Check if skipping
             if chr.is_ascii_digit() {
-                let digit = chr.to_digit(10).unwrap();
-                col += digit;
+                col += read_number(chr, &mut chars);
+                continue;
+            }
+            // A promoted piece, which Crazyhouse turns back into a pawn when captured
+            if chr == '~' {
+                if let Some(piece) = col
+                    .checked_sub(1)
+                    .and_then(|prev_col| game.board[row][prev_col].as_mut())
+                {
+                    piece.promoted = true;
+                }
                 continue;
             }
             // Check if next line
@@ -29,12 +75,22 @@ impl Game {
 
             // Make piece
             let p = alg_to_piece(chr);
+            if let PieceType::Fairy(letter) = p.piece_type
+                && game.fairy_piece(letter).is_none()
+            {
+                return Err(Error::other(format!(
+                    "There is no piece {} in {}",
+                    chr,
+                    variant.name()
+                )));
+            }
             let mut has_moved = false;
+            // Pawns on their first rank, as in Horde, can still move two squares
             if p.piece_type == PieceType::Pawn {
-                if p.color == Color::White && row != 1 {
+                if p.color == Color::White && row > game.pawn_row(Color::White) {
                     has_moved = true;
                 }
-                if p.color == Color::Black && row != 6 {
+                if p.color == Color::Black && row < game.pawn_row(Color::Black) {
                     has_moved = true;
                 }
             }
@@ -42,28 +98,155 @@ impl Game {
                 color: p.color,
                 piece_type: p.piece_type,
                 has_moved: has_moved,
+                promoted: false,
             };
-            game.board[row as usize][col as usize] = Some(my_piece);
+            game.board[row][col] = Some(my_piece);
             col += 1;
         }
+
+        if let Some(side) = fields.next() {
+            game.next_player = match side {
+                "w" => Color::White,
+                "b" => Color::Black,
+                _ => {
+                    return Err(Error::other(format!(
+                        "Side to move has to be w or b, not {}",
+                        side
+                    )));
+                }
+            };
+        }
+        let mut castling_errors = Vec::new();
+        if let Some(castling) = fields.next() {
+            castling_errors = game.set_castling_rights(castling)?;
+        }
+        if let Some(en_passant) = fields.next()
+            && en_passant != "-"
+        {
+            game.start_en_passant =
+                Some(game.notation_to_board_position(en_passant).ok_or_else(|| {
+                    Error::other(format!(
+                        "En passant square has to be a square on the board or -, not {}",
+                        en_passant
+                    ))
+                })?);
+        }
+        // The move counters, which some FENs leave out. Three-check FENs end with the checks
+        // each side has given, as +N+M.
+        let rest: Vec<&str> = fields.collect();
+        let mut counters = rest.iter().filter_map(|field| field.parse::<usize>().ok());
+        game.start_move_counters = (
+            counters.next().unwrap_or(0),
+            counters.next().unwrap_or(1).max(1),
+        );
+        if let Some(checks) = rest.iter().find_map(|field| field.strip_prefix('+')) {
+            game.start_checks = parse_checks(checks)?;
+        }
+
         game.next_legal_moves = game.get_all_legal_moves(true);
-        return Ok(game);
+        game.state = game.start_state();
+        return Ok((game, castling_errors));
+    }
+
+    // Castling is allowed as long as the king and rook haven't moved, so rights become has_moved flags.
+    // Besides KQkq, X-FEN/Shredder-FEN rook files (A-H, a-h) are accepted for Chess960.
+    fn set_castling_rights(&mut self, castling: &str) -> Result<Vec<PositionError>, Error> {
+        let last_file = (b'A' + self.cols as u8 - 1) as char;
+        if castling != "-"
+            && !castling.chars().all(|chr| {
+                "KQkq".contains(chr) || ('A'..=last_file).contains(&chr.to_ascii_uppercase())
+            })
+        {
+            return Err(Error::other(format!(
+                "Castling rights can only contain KQkq, rook files or -, not {}",
+                castling
+            )));
+        }
+
+        let mut errors = Vec::new();
+        for color in [Color::White, Color::Black] {
+            let row = self.home_row(color);
+            let is_own = |piece: Option<Piece>, piece_type| {
+                piece.is_some_and(|p| p.piece_type == piece_type && p.color == color)
+            };
+            let king_col =
+                (0..self.cols).find(|&col| is_own(self.board[row][col], PieceType::King));
+
+            let mut rook_cols = Vec::new();
+            for chr in castling.chars().filter(|&chr| chr != '-') {
+                if (color == Color::White) != chr.is_ascii_uppercase() {
+                    continue;
+                }
+                let rook_col = match (chr.to_ascii_uppercase(), king_col) {
+                    (_, None) => {
+                        if !errors.contains(&PositionError::CastlingWithoutKing(color)) {
+                            errors.push(PositionError::CastlingWithoutKing(color));
+                        }
+                        continue;
+                    }
+                    // The outermost rook on that side of the king
+                    ('K', Some(king_col)) => (king_col + 1..self.cols)
+                        .rev()
+                        .find(|&col| is_own(self.board[row][col], PieceType::Rook)),
+                    ('Q', Some(king_col)) => {
+                        (0..king_col).find(|&col| is_own(self.board[row][col], PieceType::Rook))
+                    }
+                    (file, _) => Some((file as u8 - b'A') as usize)
+                        .filter(|&col| is_own(self.board[row][col], PieceType::Rook)),
+                };
+                match rook_col {
+                    Some(rook_col) => rook_cols.push(rook_col),
+                    None => errors.push(PositionError::CastlingWithoutRook(color, chr)),
+                }
+            }
+
+            // Rights the classical setup can't express mean the game is Chess960
+            if (king_col.is_some_and(|col| col != self.cols / 2) && !rook_cols.is_empty())
+                || rook_cols
+                    .iter()
+                    .any(|&col| col != 0 && col != self.cols - 1)
+            {
+                self.chess960 = true;
+            }
+
+            for i in 0..self.rows {
+                for j in 0..self.cols {
+                    if let Some(piece) = self.board[i][j].as_mut()
+                        && piece.color == color
+                    {
+                        match piece.piece_type {
+                            PieceType::King => {
+                                piece.has_moved = i != row || rook_cols.is_empty();
+                            }
+                            PieceType::Rook => {
+                                piece.has_moved = i != row || !rook_cols.contains(&j);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        Ok(errors)
     }
 
     pub fn to_fen_str(&self) -> String {
         let mut result = String::new();
         let mut blanks = 0;
-        for i in (0..8).rev() {
-            for j in 0..8 {
+        for i in (0..self.rows).rev() {
+            for j in 0..self.cols {
                 let piece = self.board[i][j];
                 match piece {
                     Some(p) => {
                         let piece_name = piece_to_alg(p);
                         if blanks > 0 {
-                            result.push(char::from_digit(blanks, 10).unwrap());
+                            result.push_str(&blanks.to_string());
                             blanks = 0;
                         }
                         result.push(piece_name);
+                        if p.promoted && self.variant.has_pockets() {
+                            result.push('~');
+                        }
                     }
                     None => {
                         blanks += 1;
@@ -71,66 +254,221 @@ impl Game {
                 }
             }
             if blanks > 0 {
-                result.push(char::from_digit(blanks, 10).unwrap());
+                result.push_str(&blanks.to_string());
                 blanks = 0;
             }
             if i != 0 {
                 result.push('/');
             }
         }
+        if self.variant.has_pockets() {
+            result.push('[');
+            for color in [Color::White, Color::Black] {
+                for (piece_type, count) in self.pocket(color).pieces() {
+                    let piece_name = piece_to_alg(Piece {
+                        color,
+                        piece_type,
+                        has_moved: false,
+                        promoted: false,
+                    });
+                    result.extend(std::iter::repeat_n(piece_name, count as usize));
+                }
+            }
+            result.push(']');
+        }
         result
     }
-}
 
-fn check_fen_str(fen: &str) -> Result<(), Error> {
-    let mut line_length: u32 = 0;
-    let mut total_lines = 0;
+    // Full six field FEN: board, side to move, castling, en passant, halfmove clock, fullmove number
+    pub fn to_full_fen_str(&self) -> String {
+        let side = if self.next_player == Color::White {
+            'w'
+        } else {
+            'b'
+        };
 
-    for chr in fen.chars() {
-        if chr.is_ascii_digit() {
-            let digit = chr.to_digit(10).unwrap();
-            if digit == 0 {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "Can't put 0 as a number buddy",
-                ));
+        // X-FEN: KQkq unless another rook further out on the same side has kept its rights too
+        let mut castling = String::new();
+        for color in [Color::White, Color::Black] {
+            let rook_cols = self.castling_rook_cols(color);
+            let king_col = (0..self.cols).find(|&col| {
+                self.board[self.home_row(color)][col]
+                    .is_some_and(|p| p.piece_type == PieceType::King && p.color == color)
+            });
+            let mut rights = Vec::new();
+            for &rook_col in rook_cols.iter().rev() {
+                let king_side = king_col.is_some_and(|king_col| rook_col > king_col);
+                let outermost = if king_side {
+                    rook_cols.iter().all(|&col| col <= rook_col)
+                } else {
+                    rook_cols.iter().all(|&col| col >= rook_col)
+                };
+                let chr = match (outermost, king_side) {
+                    (true, true) => 'K',
+                    (true, false) => 'Q',
+                    (false, _) => (b'A' + rook_col as u8) as char,
+                };
+                rights.push(chr);
             }
-            if digit > 8 {
-                return Err(Error::new(ErrorKind::Other, "Number can't be more than 8"));
-            } else {
-                line_length += digit;
-                continue;
+            // Kingside rights before queenside ones
+            rights.sort_by_key(|&chr| match chr {
+                'K' => 0,
+                'Q' => 2,
+                _ => 1,
+            });
+            for chr in rights {
+                castling.push(if color == Color::White {
+                    chr
+                } else {
+                    chr.to_ascii_lowercase()
+                });
             }
         }
-        if chr == FEN_LINE_SEPARATOR {
-            if line_length != 8 {
-                if line_length < 8 {
-                    return Err(Error::new(ErrorKind::Other, "Line length too short buddy"));
-                } else {
-                    return Err(Error::new(ErrorKind::Other, "Line length too long buddy"));
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = match self.move_history.last() {
+            Some(Move::Normal(mv))
+                if mv.piece.piece_type == PieceType::Pawn
+                    && mv.from_position.0 == self.pawn_row(mv.piece.color)
+                    && mv.from_position.0.abs_diff(mv.to_position.0) == 2 =>
+            {
+                board_position_to_notation(
+                    (mv.from_position.0 + mv.to_position.0) / 2,
+                    mv.from_position.1,
+                )
+            }
+            None => self.start_en_passant.map_or("-".to_string(), |(row, col)| {
+                board_position_to_notation(row, col)
+            }),
+            _ => "-".to_string(),
+        };
+
+        let halfmove_clock = self
+            .move_history
+            .iter()
+            .rev()
+            .take_while(|mv| match mv {
+                Move::Normal(mv) => mv.capture.is_none() && mv.piece.piece_type != PieceType::Pawn,
+                Move::Castles(_) => true,
+                _ => false,
+            })
+            .count();
+        // Carried on from the starting position while nothing has reset it
+        let halfmove_clock = if halfmove_clock == self.move_history.len() {
+            halfmove_clock + self.start_move_counters.0
+        } else {
+            halfmove_clock
+        };
+
+        // Plies since White's first move, counting the side that started
+        let started_with_black = self.move_history.len().is_multiple_of(2) == (side == 'b');
+        let plies = (self.start_move_counters.1 - 1) * 2
+            + self.move_history.len()
+            + started_with_black as usize;
+
+        let mut fen = format!(
+            "{} {} {} {} {} {}",
+            self.to_fen_str(),
+            side,
+            castling,
+            en_passant,
+            halfmove_clock,
+            plies / 2 + 1
+        );
+        if self.variant.checks_to_win().is_some() {
+            fen.push_str(&format!(
+                " +{}+{}",
+                self.checks_given(Color::White),
+                self.checks_given(Color::Black)
+            ));
+        }
+        fen
+    }
+}
+
+// "N+M", after the leading +
+fn parse_checks(checks: &str) -> Result<(u32, u32), Error> {
+    let invalid = || {
+        Error::other(format!(
+            "Check counts have to look like +N+M, not +{}",
+            checks
+        ))
+    };
+    let (white, black) = checks.split_once('+').ok_or_else(invalid)?;
+    Ok((
+        white.parse().map_err(|_| invalid())?,
+        black.parse().map_err(|_| invalid())?,
+    ))
+}
+
+// Every rank has to have the same number of files. Returns the number of ranks and files.
+fn check_fen_str(fen: &str) -> Result<(usize, usize), Error> {
+    let mut line_lengths = Vec::new();
+
+    for line in fen.split(FEN_LINE_SEPARATOR) {
+        let mut line_length = 0;
+        let mut chars = line.chars().peekable();
+        while let Some(chr) = chars.next() {
+            if chr.is_ascii_digit() {
+                let number = read_number(chr, &mut chars);
+                if number == 0 {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        "Can't put 0 as a number buddy",
+                    ));
                 }
-            } else {
-                line_length = 0;
-                total_lines += 1;
+                line_length += number;
                 continue;
             }
+            if chr == '~' {
+                continue;
+            }
+            // Letters besides the standard pieces' are fairy pieces, which the variant checks
+            if !chr.is_ascii_alphabetic() {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!(
+                        "Char: {} cannot be in the string. It has to be a piece letter, a digit, / or ~",
+                        chr,
+                    ),
+                ));
+            }
+            line_length += 1;
         }
-        if !ALLOWED_LOWERCASE_CHARS.contains(&chr.to_ascii_lowercase()) {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!(
-                    "Char: {} cannot be in the string. The character(in lowercase) has to be one of {:?}",
-                    chr, ALLOWED_LOWERCASE_CHARS,
-                ),
-            ));
+        if let Some(&first_length) = line_lengths.first() {
+            if line_length < first_length {
+                return Err(Error::new(ErrorKind::Other, "Line length too short buddy"));
+            }
+            if line_length > first_length {
+                return Err(Error::new(ErrorKind::Other, "Line length too long buddy"));
+            }
         }
-        line_length += 1;
+        line_lengths.push(line_length);
     }
 
-    if total_lines != 7 || line_length != 8 {
+    let (rows, cols) = (line_lengths.len(), line_lengths[0]);
+    if rows < MIN_BOARD_SIZE || cols < MIN_BOARD_SIZE {
         return Err(Error::new(ErrorKind::Other, "Too few lines here buddy"));
     }
-    return Ok(());
+    if rows > MAX_BOARD_SIZE || cols > MAX_BOARD_SIZE {
+        return Err(Error::other(format!(
+            "The board can't be bigger than {}x{}",
+            MAX_BOARD_SIZE, MAX_BOARD_SIZE
+        )));
+    }
+    return Ok((rows, cols));
+}
+
+// A run of empty squares, which takes two digits on boards wider than 9 files
+fn read_number(first_digit: char, chars: &mut Peekable<Chars>) -> usize {
+    let mut number = first_digit.to_digit(10).unwrap() as usize;
+    while let Some(digit) = chars.peek().and_then(|chr| chr.to_digit(10)) {
+        number = number * 10 + digit as usize;
+        chars.next();
+    }
+    number
 }
 
 pub fn piece_to_alg(piece: Piece) -> char {
@@ -141,6 +479,7 @@ pub fn piece_to_alg(piece: Piece) -> char {
         PieceType::Bishop => 'b',
         PieceType::King => 'k',
         PieceType::Queen => 'q',
+        PieceType::Fairy(letter) => letter.to_ascii_lowercase(),
     };
     if piece.color == Color::White {
         piece_name = piece_name.to_ascii_uppercase();
@@ -161,13 +500,12 @@ pub fn alg_to_piece(name: char) -> Piece {
         'b' => PieceType::Bishop,
         'k' => PieceType::King,
         'q' => PieceType::Queen,
-        _ => {
-            panic!("How tf did this happen? What is that piece?")
-        }
+        _ => PieceType::Fairy(name.to_ascii_uppercase()),
     };
     Piece {
         color,
         piece_type: p_type,
         has_moved: false,
+        promoted: false,
     }
 }
@@ -0,0 +1,344 @@
+use super::game::*;
+use super::moves::*;
+use super::piece::*;
+use std::time::{Duration, Instant};
+
+pub const MATE_SCORE: i32 = 100_000;
+const INFINITY: i32 = 1_000_000;
+
+// Captures are searched past the depth limit, but not forever
+const MAX_QUIESCENCE_DEPTH: u32 = 4;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub time: Option<Duration>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // Centipawns from the point of view of the side to move
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+struct SearchContext {
+    deadline: Option<Instant>,
+    nodes: u64,
+    stopped: bool,
+}
+
+impl SearchContext {
+    fn out_of_time(&mut self) -> bool {
+        if !self.stopped
+            && let Some(deadline) = self.deadline
+        {
+            self.stopped = Instant::now() >= deadline;
+        }
+        self.stopped
+    }
+}
+
+impl Game {
+    pub fn piece_value(&self, piece_type: PieceType) -> i32 {
+        match piece_type {
+            PieceType::Pawn => 100,
+            PieceType::Knight => 320,
+            PieceType::Bishop => 330,
+            PieceType::Rook => 500,
+            PieceType::Queen => 900,
+            PieceType::King => 0,
+            PieceType::Fairy(letter) => self
+                .fairy_piece(letter)
+                .map_or(0, |definition| definition.value),
+        }
+    }
+
+    // Iterative deepening alpha-beta search. Without a depth or time limit it stops at depth 3.
+    pub fn search(&self, limits: SearchLimits) -> SearchResult {
+        let max_depth = match (limits.depth, limits.time) {
+            (Some(depth), _) => depth.max(1),
+            (None, Some(_)) => u32::MAX,
+            (None, None) => 3,
+        };
+        let mut ctx = SearchContext {
+            deadline: limits.time.map(|time| Instant::now() + time),
+            nodes: 0,
+            stopped: false,
+        };
+        let mut result = SearchResult {
+            best_move: self.next_legal_moves.first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+
+        for depth in 1..=max_depth {
+            let (score, pv) = self.search_root(depth, &result.pv, &mut ctx);
+            // An interrupted iteration is only partially searched, so keep the previous one
+            if ctx.stopped && depth > 1 {
+                break;
+            }
+            result.score = score;
+            result.depth = depth;
+            result.best_move = pv.first().copied();
+            result.pv = pv;
+            if ctx.stopped || is_mate_score(score) {
+                break;
+            }
+        }
+        result.nodes = ctx.nodes;
+        result
+    }
+
+    fn search_root(
+        &self,
+        depth: u32,
+        previous_pv: &[Move],
+        ctx: &mut SearchContext,
+    ) -> (i32, Vec<Move>) {
+        // Mated or stalemated already, which the loop below wouldn't score
+        if self.next_legal_moves.is_empty() {
+            return (self.clone().game_over_score(0), Vec::new());
+        }
+        // Root moves come from next_legal_moves so callers can compare them directly
+        let mut moves = self.next_legal_moves.clone();
+        self.order_moves(&mut moves, previous_pv.first());
+
+        let mut alpha = -INFINITY;
+        let mut best_pv = Vec::new();
+        for mv in moves {
+            let mut child = self.clone();
+            child.make_search_move(mv);
+            let (score, mut pv) = child.negamax(
+                depth - 1,
+                1,
+                -INFINITY,
+                -alpha,
+                previous_pv.get(1..).unwrap_or(&[]),
+                ctx,
+            );
+            let score = -score;
+            if ctx.stopped && !best_pv.is_empty() {
+                break;
+            }
+            if score > alpha || best_pv.is_empty() {
+                alpha = score;
+                pv.insert(0, mv);
+                best_pv = pv;
+            }
+        }
+        (alpha, best_pv)
+    }
+
+    fn negamax(
+        &mut self,
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        previous_pv: &[Move],
+        ctx: &mut SearchContext,
+    ) -> (i32, Vec<Move>) {
+        ctx.nodes += 1;
+        if ctx.out_of_time() {
+            return (0, Vec::new());
+        }
+
+        if self.next_legal_moves.is_empty() {
+            return (self.game_over_score(ply), Vec::new());
+        }
+        if depth == 0 {
+            return (self.quiescence(ply, alpha, beta, 0, ctx), Vec::new());
+        }
+
+        let mut moves = self.next_legal_moves.clone();
+        self.order_moves(&mut moves, previous_pv.first());
+
+        let mut best_pv = Vec::new();
+        for mv in moves {
+            let mut child = self.clone();
+            child.make_search_move(mv);
+            let (score, mut pv) = child.negamax(
+                depth - 1,
+                ply + 1,
+                -beta,
+                -alpha,
+                previous_pv.get(1..).unwrap_or(&[]),
+                ctx,
+            );
+            let score = -score;
+            if ctx.stopped {
+                return (0, Vec::new());
+            }
+            if score > alpha {
+                alpha = score;
+                pv.insert(0, mv);
+                best_pv = pv;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        (alpha, best_pv)
+    }
+
+    // Score for the side to move once it has no moves left, either because the last move
+    // ended the game or because of mate or stalemate
+    fn game_over_score(&mut self, ply: i32) -> i32 {
+        let variant = self.variant;
+        let color = self.next_player;
+        let state = if self.state.is_over() {
+            self.state
+        } else {
+            let in_check = variant.in_check(self, color);
+            variant.no_moves_state(color, in_check)
+        };
+        match state {
+            GameState::Checkmate(loser) if loser == color => -MATE_SCORE + ply,
+            GameState::Checkmate(_) => MATE_SCORE - ply,
+            GameState::VariantWin(winner) if winner == color => MATE_SCORE - ply,
+            GameState::VariantWin(_) => -MATE_SCORE + ply,
+            _ => 0,
+        }
+    }
+
+    // Only look at captures so the static evaluation isn't taken in the middle of an exchange
+    fn quiescence(
+        &mut self,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        qdepth: u32,
+        ctx: &mut SearchContext,
+    ) -> i32 {
+        ctx.nodes += 1;
+        let stand_pat = self.evaluate();
+        if stand_pat >= beta || qdepth >= MAX_QUIESCENCE_DEPTH || ctx.out_of_time() {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut captures: Vec<Move> = self
+            .next_legal_moves
+            .iter()
+            .copied()
+            .filter(|mv| self.capture_value(mv) > 0)
+            .collect();
+        self.order_moves(&mut captures, None);
+
+        for mv in captures {
+            let mut child = self.clone();
+            child.make_search_move(mv);
+            if child.next_legal_moves.is_empty() {
+                alpha = alpha.max(-child.game_over_score(ply + 1));
+            } else {
+                alpha = alpha.max(-child.quiescence(ply + 1, -beta, -alpha, qdepth + 1, ctx));
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        alpha
+    }
+
+    // Material plus a small bonus for pieces near the centre, from the side to move's view
+    pub fn evaluate(&self) -> i32 {
+        let mut score = 0;
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                if let Some(piece) = self.board[i][j] {
+                    let centre_distance = (2 * i as i32 - self.rows as i32 + 1).abs()
+                        + (2 * j as i32 - self.cols as i32 + 1).abs();
+                    let centre_bonus = match piece.piece_type {
+                        PieceType::Knight | PieceType::Bishop | PieceType::Pawn => {
+                            14 - centre_distance
+                        }
+                        _ => 0,
+                    };
+                    let value = self.piece_value(piece.piece_type) + centre_bonus;
+                    if piece.color == self.next_player {
+                        score += value;
+                    } else {
+                        score -= value;
+                    }
+                }
+            }
+        }
+
+        // Crazyhouse pieces in hand are worth as much as on the board
+        for (color, sign) in [(self.next_player, 1), (self.next_player.opposite(), -1)] {
+            for (piece_type, count) in self.pocket(color).pieces() {
+                score += sign * self.piece_value(piece_type) * count as i32;
+            }
+        }
+        score
+    }
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() > MATE_SCORE - 1000
+}
+
+impl Game {
+    fn capture_value(&self, mv: &Move) -> i32 {
+        match mv {
+            Move::Normal(mv) => mv
+                .capture
+                .map_or(0, |piece| self.piece_value(piece.piece_type)),
+            Move::Promotion(mv) => {
+                mv.capture
+                    .map_or(0, |piece| self.piece_value(piece.piece_type))
+                    + self.piece_value(mv.new_piece.piece_type)
+            }
+            Move::EnPassant(_) => self.piece_value(PieceType::Pawn),
+            Move::Castles(_) | Move::Drop(_) => 0,
+        }
+    }
+
+    fn moving_piece_value(&self, mv: &Move) -> i32 {
+        match mv {
+            Move::Normal(mv) => self.piece_value(mv.piece.piece_type),
+            _ => self.piece_value(PieceType::Pawn),
+        }
+    }
+
+    // Previous best move first, then captures of the most valuable piece by the least valuable one
+    fn order_moves(&self, moves: &mut [Move], first: Option<&Move>) {
+        moves.sort_by_key(|mv| {
+            if Some(mv) == first {
+                return i32::MIN;
+            }
+            let capture = self.capture_value(mv);
+            if capture > 0 {
+                -(capture * 10 - self.moving_piece_value(mv))
+            } else {
+                0
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_without_moves_score_as_their_result() {
+        let limits = SearchLimits {
+            depth: Some(2),
+            time: None,
+        };
+        let stalemate = Game::from_fen_str("k7/8/1Q6/8/8/8/8/7K b - - 0 1").unwrap();
+        let result = stalemate.search(limits);
+        assert_eq!((result.score, result.best_move), (0, None));
+        assert!(!is_mate_score(result.score));
+
+        let mate = Game::from_fen_str("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(mate.search(limits).score, -MATE_SCORE);
+    }
+}
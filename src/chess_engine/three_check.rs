@@ -0,0 +1,103 @@
+use super::game::{Color, Game, GameState};
+use super::variant::Variant;
+
+const CHECKS_TO_WIN: u32 = 3;
+
+// Standard chess, except giving a third check wins
+#[derive(Debug)]
+pub struct ThreeCheck;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "Three-check"
+    }
+
+    fn decisive_state(&self, after: &Game, color: Color, gives_check: bool) -> Option<GameState> {
+        // The move just played hasn't got its state yet, so it isn't counted by checks_given
+        let checks = after.checks_given(color) + gives_check as u32;
+        (checks >= CHECKS_TO_WIN).then_some(GameState::VariantWin(color))
+    }
+
+    fn checks_to_win(&self) -> Option<u32> {
+        Some(CHECKS_TO_WIN)
+    }
+}
+
+impl Game {
+    // Checks given by `color` since the start of the game, including any from the FEN
+    pub fn checks_given(&self, color: Color) -> u32 {
+        let before_start = match color {
+            Color::White => self.start_checks.0,
+            Color::Black => self.start_checks.1,
+        };
+        // A win in a variant that counts checks is the check that won it
+        let counts_checks = self.variant.checks_to_win().is_some();
+        let in_game = self
+            .move_history
+            .iter()
+            .filter(|mv| match mv.get_state() {
+                GameState::InCheck(checked) | GameState::Checkmate(checked) => checked != color,
+                GameState::VariantWin(winner) => counts_checks && winner == color,
+                _ => false,
+            })
+            .count();
+        before_start + in_game as u32
+    }
+
+    // Checks `color` still has to give to win, in variants that count them
+    pub fn checks_remaining(&self, color: Color) -> Option<u32> {
+        let to_win = self.variant.checks_to_win()?;
+        Some(to_win.saturating_sub(self.checks_given(color)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FenValidation;
+
+    fn play(game: &mut Game, sans: &str) {
+        for san in sans.split_whitespace() {
+            let mv = game.move_from_san(san).expect("Test move is legal");
+            game.make_move(mv);
+        }
+    }
+
+    #[test]
+    fn third_check_wins_and_undoing_it_counts_back() {
+        let mut game = Game::from_variant(&ThreeCheck);
+        play(&mut game, "e4 e5 Bc4 Nc6 Bxf7+ Kxf7 Qh5+ Ke7");
+        assert_eq!(game.checks_given(Color::White), 2);
+        assert_eq!(game.checks_given(Color::Black), 0);
+        assert_eq!(game.checks_remaining(Color::White), Some(1));
+
+        play(&mut game, "Qxe5+");
+        assert_eq!(game.state, GameState::VariantWin(Color::White));
+        assert_eq!(game.checks_remaining(Color::White), Some(0));
+        assert!(game.to_full_fen_str().ends_with("+3+0"));
+
+        game.undo_move();
+        assert_eq!(game.checks_given(Color::White), 2);
+        assert_eq!(game.state, GameState::Normal);
+    }
+
+    #[test]
+    fn counts_checks_given_before_the_fen() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+        let (mut game, _) = Game::from_fen_str_validated(
+            &format!("{} +2+0", fen),
+            &ThreeCheck,
+            FenValidation::Strict,
+        )
+        .unwrap();
+        assert_eq!(game.checks_given(Color::White), 2);
+        play(&mut game, "Ra8+");
+        assert_eq!(game.state, GameState::VariantWin(Color::White));
+
+        let (mut game, _) =
+            Game::from_fen_str_validated(fen, &ThreeCheck, FenValidation::Strict).unwrap();
+        play(&mut game, "Ra8+");
+        assert_eq!(game.state, GameState::InCheck(Color::Black));
+        assert!(game.to_full_fen_str().ends_with("+1+0"));
+    }
+}
@@ -0,0 +1,307 @@
+use super::game::{Color, Game, board_position_to_notation};
+use super::piece::{Movement, PieceType};
+use super::variant::Variant;
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+
+// Something that makes a position impossible to reach in a game
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
+pub enum PositionError {
+    // A side with some other number of kings than one
+    KingCount(Color, usize),
+    // A pawn on its side's first or last rank
+    PawnOnBackRank(Color, (usize, usize)),
+    // The side that just moved left its own king in check
+    OpponentInCheck(Color),
+    // The side to move is checked in a way no single move could have done
+    ImpossibleCheck(Color),
+    // An en passant square without a pawn that just moved two squares past it
+    ImpossibleEnPassant((usize, usize)),
+    // Castling rights in the FEN for a side whose king isn't on its first rank
+    CastlingWithoutKing(Color),
+    // A castling right in the FEN, as written there, without a rook to castle with
+    CastlingWithoutRook(Color, char),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::KingCount(color, count) => {
+                write!(f, "{} has {} kings instead of one", color, count)
+            }
+            PositionError::PawnOnBackRank(color, (row, col)) => write!(
+                f,
+                "{} pawn on {} can't be on the first or last rank",
+                color,
+                board_position_to_notation(*row, *col)
+            ),
+            PositionError::OpponentInCheck(color) => write!(
+                f,
+                "{} is in check, but it is {}'s move",
+                color,
+                color.opposite()
+            ),
+            PositionError::ImpossibleCheck(color) => write!(
+                f,
+                "{} is checked by pieces that couldn't all have given check with one move",
+                color
+            ),
+            PositionError::ImpossibleEnPassant((row, col)) => write!(
+                f,
+                "En passant square {} doesn't follow a pawn moving two squares",
+                board_position_to_notation(*row, *col)
+            ),
+            PositionError::CastlingWithoutKing(color) => write!(
+                f,
+                "{} has castling rights but no king on its first rank",
+                color
+            ),
+            PositionError::CastlingWithoutRook(color, right) => write!(
+                f,
+                "{} has castling right {} but no rook to castle with",
+                color, right
+            ),
+        }
+    }
+}
+
+// How much FEN import minds a position that couldn't come up in a game
+#[derive(Copy, Clone, Debug, Default, PartialEq, Hash, Eq)]
+pub enum FenValidation {
+    // Read it anyway, dropping castling rights and en passant squares that can't be used
+    #[default]
+    Lenient,
+    // Refuse it
+    Strict,
+}
+
+impl Game {
+    // Reads the FEN as a position of `variant` along with every problem it has. In strict
+    // mode a position with any problem is an error instead.
+    pub fn from_fen_str_validated(
+        fen: &str,
+        variant: &'static dyn Variant,
+        validation: FenValidation,
+    ) -> Result<(Game, Vec<PositionError>), io::Error> {
+        let (mut game, mut errors) = Game::read_fen(fen, variant)?;
+        errors.extend(game.validate());
+        errors.retain(|error| !variant.allows_position_error(error));
+
+        if validation == FenValidation::Strict && !errors.is_empty() {
+            let problems: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return Err(Error::new(ErrorKind::InvalidData, problems.join("; ")));
+        }
+        if errors
+            .iter()
+            .any(|error| matches!(error, PositionError::ImpossibleEnPassant(_)))
+        {
+            game.start_en_passant = None;
+            game.next_legal_moves = game.get_all_legal_moves(true);
+        }
+        Ok((game, errors))
+    }
+
+    // Every reason the position couldn't have come up in a game of its variant. Castling
+    // rights only exist as far as the pieces allow them, so only FEN import checks those.
+    pub fn validate(&self) -> Vec<PositionError> {
+        let mut errors = Vec::new();
+
+        let mut king_counts = [0; 2];
+        for (index, color) in [Color::White, Color::Black].into_iter().enumerate() {
+            king_counts[index] = self.king_positions(color).count();
+            if king_counts[index] != 1 {
+                errors.push(PositionError::KingCount(color, king_counts[index]));
+            }
+        }
+
+        for i in [0, self.rows - 1] {
+            for j in 0..self.cols {
+                if let Some(piece) = self.board[i][j]
+                    && piece.piece_type == PieceType::Pawn
+                {
+                    errors.push(PositionError::PawnOnBackRank(piece.color, (i, j)));
+                }
+            }
+        }
+
+        let opponent = self.next_player.opposite();
+        if self.variant.in_check(&mut self.clone(), opponent) {
+            errors.push(PositionError::OpponentInCheck(opponent));
+        }
+
+        if king_counts == [1, 1] && self.impossible_check() {
+            errors.push(PositionError::ImpossibleCheck(self.next_player));
+        }
+
+        if let Some(square) = self.start_en_passant
+            && !self.possible_en_passant(square)
+        {
+            errors.push(PositionError::ImpossibleEnPassant(square));
+        }
+
+        errors.retain(|error| !self.variant.allows_position_error(error));
+        errors
+    }
+
+    fn king_positions(&self, color: Color) -> impl Iterator<Item = (usize, usize)> {
+        (0..self.rows)
+            .flat_map(move |i| (0..self.cols).map(move |j| (i, j)))
+            .filter(move |&(i, j)| {
+                self.board[i][j]
+                    .is_some_and(|p| p.piece_type == PieceType::King && p.color == color)
+            })
+    }
+
+    // A move gives check with the piece that moved and at most one more that it uncovered,
+    // which has to be a slider. Both can't stand on the same line through the king either.
+    fn impossible_check(&self) -> bool {
+        let color = self.next_player;
+        if !self.variant.in_check(&mut self.clone(), color) {
+            return false;
+        }
+        let Some(king) = self.king_positions(color).next() else {
+            return false;
+        };
+
+        let mut checkers: Vec<(usize, usize)> = self
+            .get_all_moves(color.opposite())
+            .into_iter()
+            .filter(|mv| mv.get_to_position() == king && mv.is_capture())
+            .map(|mv| mv.get_from_position())
+            .collect();
+        checkers.sort();
+        checkers.dedup();
+
+        match checkers[..] {
+            [first, second] => {
+                let is_slider = |(i, j): (usize, usize)| {
+                    self.board[i][j].is_some_and(|p| {
+                        self.movements(p.piece_type)
+                            .iter()
+                            .any(|movement| matches!(movement, Movement::Rider(..)))
+                    })
+                };
+                let offset =
+                    |(i, j): (usize, usize)| (i as i32 - king.0 as i32, j as i32 - king.1 as i32);
+                let ((a, b), (c, d)) = (offset(first), offset(second));
+                let on_one_line = a * d == b * c;
+                on_one_line || !(is_slider(first) || is_slider(second))
+            }
+            _ => checkers.len() > 2,
+        }
+    }
+
+    // The square has to be empty, with the pawn that passed over it from its side's pawn
+    // row just in front of it and the square it came from empty behind it
+    fn possible_en_passant(&self, (row, col): (usize, usize)) -> bool {
+        let mover = self.next_player.opposite();
+        let (pawn_row, from_row) = match mover {
+            Color::White => (row + 1, row.checked_sub(1)),
+            Color::Black => (row.wrapping_sub(1), Some(row + 1)),
+        };
+        let Some(from_row) = from_row else {
+            return false;
+        };
+        from_row == self.pawn_row(mover)
+            && self.valid_position((pawn_row, col))
+            && self.valid_position((from_row, col))
+            && self.board[row][col].is_none()
+            && self.board[from_row][col].is_none()
+            && self.board[pawn_row][col]
+                .is_some_and(|p| p.piece_type == PieceType::Pawn && p.color == mover)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::Standard;
+
+    fn errors(fen: &str) -> Vec<PositionError> {
+        Game::from_fen_str_validated(fen, &Standard, FenValidation::Lenient)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn legal_positions_have_no_errors() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1",
+            // A knight moving off the e file uncovers the rook's check
+            "4k3/8/5N2/8/8/8/8/4R1K1 b - - 0 1",
+        ] {
+            assert_eq!(errors(fen), [], "{}", fen);
+        }
+    }
+
+    #[test]
+    fn king_count() {
+        assert_eq!(
+            errors("8/8/8/8/8/8/8/K7 w - - 0 1"),
+            [PositionError::KingCount(Color::Black, 0)]
+        );
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/K6K w - - 0 1"),
+            [PositionError::KingCount(Color::White, 2)]
+        );
+    }
+
+    #[test]
+    fn pawn_on_back_rank() {
+        assert_eq!(
+            errors("P3k3/8/8/8/8/8/8/4K2p w - - 0 1"),
+            [
+                PositionError::PawnOnBackRank(Color::Black, (0, 7)),
+                PositionError::PawnOnBackRank(Color::White, (7, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn opponent_in_check() {
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            [PositionError::OpponentInCheck(Color::Black)]
+        );
+    }
+
+    #[test]
+    fn impossible_check() {
+        // Two knights can't both have just given check
+        assert_eq!(
+            errors("4k3/8/3N1N2/8/8/8/8/4K3 b - - 0 1"),
+            [PositionError::ImpossibleCheck(Color::Black)]
+        );
+        // Nor can two rooks on the same line through the king
+        assert_eq!(
+            errors("R3k2R/8/8/8/8/8/8/4K3 b - - 0 1"),
+            [PositionError::ImpossibleCheck(Color::Black)]
+        );
+    }
+
+    #[test]
+    fn impossible_en_passant() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 b - e3 0 1";
+        assert_eq!(errors(fen), [PositionError::ImpossibleEnPassant((2, 4))]);
+        // Lenient import drops the square, strict import refuses the position
+        let game = Game::from_fen_str(fen).unwrap();
+        assert_eq!(game.start_en_passant, None);
+        assert!(Game::from_fen_str_validated(fen, &Standard, FenValidation::Strict).is_err());
+    }
+
+    #[test]
+    fn castling_without_king_or_rook() {
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/4K3/R6R w KQ - 0 1"),
+            [PositionError::CastlingWithoutKing(Color::White)]
+        );
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/4K2R w KQ - 0 1"),
+            [PositionError::CastlingWithoutRook(Color::White, 'Q')]
+        );
+        // The rights are dropped, so the king can't castle queenside
+        let game = Game::from_fen_str("4k3/8/8/8/8/8/8/4K2R w KQ - 0 1").unwrap();
+        assert_eq!(game.to_full_fen_str(), "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+    }
+}
@@ -0,0 +1,212 @@
+use super::antichess::Antichess;
+use super::atomic::Atomic;
+use super::capablanca::Capablanca;
+use super::crazyhouse::Crazyhouse;
+use super::game::{Color, Game, GameState};
+use super::grand::Grand;
+use super::horde::Horde;
+use super::king_of_the_hill::KingOfTheHill;
+use super::moves::Move;
+use super::piece::{PieceDefinition, PieceType};
+use super::racing_kings::RacingKings;
+use super::three_check::ThreeCheck;
+use super::validate::PositionError;
+use std::fmt;
+
+pub const STANDARD_START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+pub const STANDARD_PROMOTIONS: [PieceType; 4] = [
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Rook,
+    PieceType::Queen,
+];
+
+// The rules a variant can change. Every method defaults to standard chess, so a variant
+// only overrides the parts it plays differently.
+pub trait Variant: fmt::Debug + Send + Sync {
+    // As written in the PGN Variant tag
+    fn name(&self) -> &'static str;
+
+    fn start_fen(&self) -> &'static str {
+        STANDARD_START_FEN
+    }
+
+    // Pieces the variant plays with besides the standard ones
+    fn fairy_pieces(&self) -> &[PieceDefinition] {
+        &[]
+    }
+
+    // How many rows in front of the back rank pawns start
+    fn pawn_rows(&self) -> usize {
+        1
+    }
+
+    // Moves the piece on the square could make, before checking they are legal
+    fn piece_moves(&self, game: &Game, from_position: (usize, usize)) -> Vec<Move> {
+        game.get_piece_moves(from_position)
+    }
+
+    // Piece types a pawn of `color` reaching `to_row` can promote to, and whether it may
+    // stay a pawn there instead
+    fn promotions(&self, game: &Game, color: Color, to_row: usize) -> (Vec<PieceType>, bool) {
+        if to_row == game.last_row(color) {
+            (STANDARD_PROMOTIONS.to_vec(), false)
+        } else {
+            (Vec::new(), true)
+        }
+    }
+
+    // Moves that don't start from a piece on the board, like Crazyhouse drops
+    fn extra_moves(&self, _game: &Game, _color: Color) -> Vec<Move> {
+        Vec::new()
+    }
+
+    // Whether captured pieces go to the capturer's pocket
+    fn has_pockets(&self) -> bool {
+        false
+    }
+
+    // Narrow down the legal moves as a whole, like making captures compulsory
+    fn restrict_moves(&self, legal_moves: Vec<Move>) -> Vec<Move> {
+        legal_moves
+    }
+
+    // Changes to the position once a move is on the board, like filling Crazyhouse pockets
+    // or Atomic explosions
+    fn after_move(&self, _game: &mut Game, _mv: Move) {}
+
+    // Takes back what `after_move` did, before the move itself is unmade
+    fn undo_move(&self, _game: &mut Game, _mv: Move) {}
+
+    // Whether the move `color` just played to reach `after` was allowed
+    fn is_legal(&self, after: &mut Game, mv: Move, color: Color) -> bool {
+        !after.king_attacked_after(mv, color)
+    }
+
+    fn in_check(&self, game: &mut Game, color: Color) -> bool {
+        game.in_check(color)
+    }
+
+    // A result the move decides by itself, whatever replies are left
+    fn decisive_state(
+        &self,
+        _after: &Game,
+        _color: Color,
+        _gives_check: bool,
+    ) -> Option<GameState> {
+        None
+    }
+
+    // The result when `color` is to move and has no legal moves
+    fn no_moves_state(&self, color: Color, in_check: bool) -> GameState {
+        if in_check {
+            GameState::Checkmate(color)
+        } else {
+            GameState::Stalemate
+        }
+    }
+
+    // Squares the GUI marks as goals, like the hill in King of the Hill
+    fn goal_squares(&self) -> Vec<(usize, usize)> {
+        Vec::new()
+    }
+
+    // Checks a player has to give to win, for variants that count them
+    fn checks_to_win(&self) -> Option<u32> {
+        None
+    }
+
+    // Whether a problem `Game::validate` finds is fine under the variant's rules
+    fn allows_position_error(&self, _error: &PositionError) -> bool {
+        false
+    }
+}
+
+#[derive(Debug)]
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+}
+
+// Every variant the engine can play, in the order the GUI cycles through them
+pub static VARIANTS: [&dyn Variant; 10] = [
+    &Standard,
+    &KingOfTheHill,
+    &ThreeCheck,
+    &Crazyhouse,
+    &Atomic,
+    &Antichess,
+    &Horde,
+    &RacingKings,
+    &Capablanca,
+    &Grand,
+];
+
+// Looks a variant up by name, ignoring case, spaces, dashes and underscores
+pub fn variant_from_name(name: &str) -> Option<&'static dyn Variant> {
+    let simplify = |name: &str| {
+        name.chars()
+            .filter(|chr| !matches!(chr, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let name = simplify(name);
+    VARIANTS
+        .iter()
+        .find(|variant| simplify(variant.name()) == name)
+        .copied()
+}
+
+pub fn is_standard(variant: &dyn Variant) -> bool {
+    variant.name() == Standard.name()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taking_every_move_back_restores_the_start() {
+        for variant in VARIANTS {
+            let mut game = Game::from_variant(variant);
+            let start = game.clone();
+            // Captures when there are any, so pockets fill up and pieces explode
+            for ply in 0..30 {
+                let moves = &game.next_legal_moves;
+                let Some(&mv) = moves
+                    .iter()
+                    .find(|mv| mv.is_capture())
+                    .or_else(|| moves.get(ply * 7 % moves.len().max(1)))
+                else {
+                    break;
+                };
+                game.make_search_move(mv);
+            }
+            assert!(!game.move_history.is_empty(), "{}", variant.name());
+
+            while game.undo_move().is_some() {}
+            assert_eq!(game.board, start.board, "{}", variant.name());
+            assert_eq!(game.pockets, start.pockets, "{}", variant.name());
+            assert!(game.explosions.is_empty(), "{}", variant.name());
+            assert_eq!(game.position_hash(), start.position_hash());
+            assert_eq!(game.next_legal_moves, start.next_legal_moves);
+        }
+    }
+
+    #[test]
+    fn finds_variants_by_loose_name() {
+        assert_eq!(
+            variant_from_name("king-of-the-hill").unwrap().name(),
+            "King of the Hill"
+        );
+        assert_eq!(
+            variant_from_name("ThreeCheck").unwrap().name(),
+            "Three-check"
+        );
+        assert!(variant_from_name("bughouse").is_none());
+    }
+}
@@ -0,0 +1,109 @@
+use super::game::*;
+use super::moves::*;
+use super::piece::*;
+
+const SIDE_TO_MOVE_KEY: u64 = 768;
+const CASTLING_KEYS: u64 = 769;
+const EN_PASSANT_KEYS: u64 = 773;
+// Rights with a rook that isn't in the corner, only possible in Chess960
+const CHESS960_CASTLING_KEYS: u64 = 781;
+// How many of each piece is in a Crazyhouse pocket
+const POCKET_KEYS: u64 = 797;
+const MAX_POCKET_COUNT: u64 = 64;
+// Pieces outside the 8x8 corner of the board and fairy pieces anywhere, after the pocket
+// keys of the 12 standard pieces, so standard positions keep the hashes saved databases use
+const LARGE_BOARD_KEYS: u64 = POCKET_KEYS + 12 * MAX_POCKET_COUNT;
+const MAX_SQUARES: u64 = (MAX_BOARD_SIZE * MAX_BOARD_SIZE) as u64;
+// Both colours of the 6 standard pieces and of a fairy piece for every letter
+const PIECE_KINDS: u64 = 12 + 2 * 26;
+// En passant files past h
+const LARGE_EN_PASSANT_KEYS: u64 = LARGE_BOARD_KEYS + PIECE_KINDS * MAX_SQUARES;
+
+// Deterministic pseudo-random key for each feature, so hashes are stable across runs
+fn zobrist_key(index: u64) -> u64 {
+    // splitmix64
+    let mut z = index.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn piece_index(piece: Piece) -> u64 {
+    let is_black = piece.color == Color::Black;
+    let type_index = match piece.piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+        // Fairy pieces come after both colours of standard pieces, by letter
+        PieceType::Fairy(letter) => {
+            return 12 + (letter as u64 - 'A' as u64) * 2 + is_black as u64;
+        }
+    };
+    type_index + is_black as u64 * 6
+}
+
+impl Game {
+    // Zobrist hash of the position: pieces, side to move, castling rights and,
+    // only when a capture is actually possible, the en passant file
+    pub fn position_hash(&self) -> u64 {
+        let mut hash = 0;
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                if let Some(piece) = self.board[i][j] {
+                    let index = piece_index(piece);
+                    let key = if index < 12 && i < 8 && j < 8 {
+                        index * 64 + (i * 8 + j) as u64
+                    } else {
+                        LARGE_BOARD_KEYS + index * MAX_SQUARES + (i * MAX_BOARD_SIZE + j) as u64
+                    };
+                    hash ^= zobrist_key(key);
+                }
+            }
+        }
+
+        if self.next_player == Color::Black {
+            hash ^= zobrist_key(SIDE_TO_MOVE_KEY);
+        }
+
+        for (k, color) in [Color::White, Color::Black].iter().enumerate() {
+            for rook_col in self.castling_rook_cols(*color) {
+                let key = match rook_col {
+                    col if col == self.cols - 1 => CASTLING_KEYS + k as u64 * 2,
+                    0 => CASTLING_KEYS + k as u64 * 2 + 1,
+                    _ => CHESS960_CASTLING_KEYS + (k * 8 + rook_col) as u64,
+                };
+                hash ^= zobrist_key(key);
+            }
+        }
+
+        if let Some(Move::EnPassant(mv)) = self
+            .next_legal_moves
+            .iter()
+            .find(|mv| matches!(mv, Move::EnPassant(_)))
+        {
+            let col = mv.to_position.1 as u64;
+            hash ^= zobrist_key(if col < 8 {
+                EN_PASSANT_KEYS + col
+            } else {
+                LARGE_EN_PASSANT_KEYS + col
+            });
+        }
+
+        for color in [Color::White, Color::Black] {
+            for (piece_type, count) in self.pocket(color).pieces() {
+                let piece = Piece {
+                    color,
+                    piece_type,
+                    has_moved: false,
+                    promoted: false,
+                };
+                hash ^=
+                    zobrist_key(POCKET_KEYS + piece_index(piece) * MAX_POCKET_COUNT + count as u64);
+            }
+        }
+        hash
+    }
+}
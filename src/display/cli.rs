@@ -1,11 +1,38 @@
-use crate::Display;
-use crate::chess_engine::{game::Game, moves::*, read_fen_string::*};
+use crate::chess_engine::{
+    eco::EcoOpening, game::Color, game::Game, read_fen_string::*, variant::VARIANTS,
+};
+use crate::{Display, UserAction};
 
 use std::io::stdin;
 
-pub struct CLIDisplayer;
+const DEFAULT_PGN_PATH: &str = "game.pgn";
+
+// Room left of the board for rank numbers, which go up to 10
+const RANK_LABEL_WIDTH: usize = 3;
+
+#[derive(Default)]
+pub struct CLIDisplayer {
+    // Black at the bottom of the board instead of White
+    flipped: bool,
+    // Opening of the last position shown, by position hash and number of moves played
+    opening: Option<(u64, usize, Option<&'static EcoOpening>)>,
+}
 
 impl CLIDisplayer {
+    // Looking the opening up goes through the whole ECO table, so it is only done again
+    // once the position changes
+    fn opening(&mut self, game: &Game) -> Option<&'static EcoOpening> {
+        let key = (game.position_hash(), game.move_history.len());
+        match self.opening {
+            Some((hash, moves, opening)) if (hash, moves) == key => opening,
+            _ => {
+                let opening = game.eco_opening();
+                self.opening = Some((key.0, key.1, opening));
+                opening
+            }
+        }
+    }
+
     fn display_moves(&self, game: &Game) {
         let possible_moves = game.mvs_to_str();
         for (i, key) in possible_moves.keys().enumerate() {
@@ -15,28 +42,33 @@ impl CLIDisplayer {
 }
 
 impl Display for CLIDisplayer {
-    fn display_message(&self, message: String) {
+    fn display_message(&mut self, message: String) {
         println!("{}", message);
     }
 
     async fn display(&mut self, game: &Game) {
-        let (n_rows, n_cols) = (game.board.len(), game.board[0].len());
+        let (n_rows, n_cols) = (game.rows, game.cols);
 
         let (row_pad, col_pad) = (1, 2);
+        let (ranks, files): (Vec<usize>, Vec<usize>) = if self.flipped {
+            ((0..n_rows).collect(), (0..n_cols).rev().collect())
+        } else {
+            ((0..n_rows).rev().collect(), (0..n_cols).collect())
+        };
 
         print_horizontal_sep(n_cols, col_pad);
 
-        for i in 0..n_rows {
+        for &i in &ranks {
             for _ in 0..row_pad {
                 print_empty_row(n_cols, col_pad);
             }
 
-            print!("|");
-            for j in 0..n_cols {
+            print!("{:>width$} |", i + 1, width = RANK_LABEL_WIDTH - 1);
+            for &j in &files {
                 for _ in 0..col_pad {
                     print!(" ");
                 }
-                let p = match game.board[n_rows - i - 1][j] {
+                let p = match game.board[i][j] {
                     Some(piece) => piece_to_alg(piece),
                     None => ' ',
                 };
@@ -55,34 +87,119 @@ impl Display for CLIDisplayer {
             print_horizontal_sep(n_cols, col_pad);
         }
 
+        print!("{:width$}", "", width = RANK_LABEL_WIDTH + 1);
+        for &j in &files {
+            let file = (b'a' + j as u8) as char;
+            print!("{:^width$} ", file, width = 2 * col_pad + 1);
+        }
+        println!();
+
+        if let Some(opening) = self.opening(game) {
+            println!("Opening: {} {}", opening.eco, opening.name);
+        }
+
+        if game.variant.has_pockets() {
+            for color in [Color::White, Color::Black] {
+                let pocket: Vec<String> = game
+                    .pocket(color)
+                    .pieces()
+                    .map(|(piece_type, count)| format!("{:?} x{}", piece_type, count))
+                    .collect();
+                println!("{} pocket: {}", color, pocket.join(", "));
+            }
+        }
+
         println!("Possible moves:");
         self.display_moves(game);
+        println!("Or type \"undo\" to take back a move, \"redo\" to replay it");
+        println!("Or type \"save [file]\" to save the game as PGN");
+        println!("Or type \"new960\" to start a new Chess960 game");
+        println!("Or type \"fen [FEN]\" to start over from a position");
+        println!("Or type \"flip\" to turn the board around");
+        println!("Or type \"quit\" to stop playing");
+        println!(
+            "Or type \"new [variant]\" to start a new game of one of: {}",
+            VARIANTS
+                .iter()
+                .map(|variant| variant.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    fn set_player_color(&mut self, color: Color) {
+        self.flipped = color == Color::Black;
     }
 
-    fn user_input(&mut self, game: &Game) -> Option<Move> {
+    async fn confirm_takeback(&mut self, _game: &Game, color: Color) -> bool {
+        println!(
+            "{}, your opponent requests a takeback. Accept? (y/n)",
+            color
+        );
+        let mut user_input = String::new();
+        stdin()
+            .read_line(&mut user_input)
+            .expect("Enter a valid string please");
+        matches!(user_input.trim(), "y" | "yes")
+    }
+
+    fn user_input(&mut self, game: &Game) -> Option<UserAction> {
         let possible_moves = game.mvs_to_str();
         let mut user_input = String::new();
 
-        stdin()
+        let read = stdin()
             .read_line(&mut user_input)
             .expect("Enter a valid string please");
+        // Nothing more to read, as when stdin is closed
+        if read == 0 {
+            return Some(UserAction::Quit);
+        }
         user_input = user_input.trim().to_string();
 
         while !(possible_moves.contains_key(&user_input)) {
+            match user_input.as_str() {
+                "undo" => return Some(UserAction::Undo),
+                "redo" => return Some(UserAction::Redo),
+                "new960" => return Some(UserAction::NewChess960Game),
+                "quit" => return Some(UserAction::Quit),
+                "flip" => {
+                    self.flipped = !self.flipped;
+                    return None;
+                }
+                _ => {}
+            }
+            if let Some(fen) = user_input.strip_prefix("fen ") {
+                return Some(UserAction::SetUpPosition(fen.trim().to_string()));
+            }
+            if let Some(name) = user_input.strip_prefix("new ") {
+                return Some(UserAction::NewGame(name.trim().to_string()));
+            }
+            if let Some(path) = user_input.strip_prefix("save") {
+                let path = match path.trim() {
+                    "" => DEFAULT_PGN_PATH,
+                    path => path,
+                };
+                return Some(UserAction::SavePgn(path.to_string()));
+            }
+
             println!("Please enter one of the valid moves:");
             self.display_moves(game);
 
             user_input.clear();
-            stdin()
+            let read = stdin()
                 .read_line(&mut user_input)
                 .expect("Enter a valid string please");
+            if read == 0 {
+                return Some(UserAction::Quit);
+            }
             user_input = user_input.trim().to_string();
         }
-        Some(possible_moves[&user_input])
+        Some(UserAction::Move(possible_moves[&user_input]))
     }
 }
 
 fn print_horizontal_sep(n_cols: usize, col_pad: usize) {
+    print!("{:width$}", "", width = RANK_LABEL_WIDTH);
     for _ in 0..(n_cols * (2 + 2 * col_pad) + 1) {
         print!("-");
     }
@@ -90,7 +207,7 @@ fn print_horizontal_sep(n_cols: usize, col_pad: usize) {
 }
 
 fn print_empty_row(n_cols: usize, col_pad: usize) {
-    print!("|");
+    print!("{:width$}|", "", width = RANK_LABEL_WIDTH);
     for _ in 0..n_cols {
         for _ in 0..(2 * col_pad + 1) {
             print!(" ");
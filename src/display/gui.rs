@@ -1,11 +1,28 @@
 use crate::{
-    Display,
+    Display, UserAction,
+    crazyhouse::POCKET_PIECES,
+    eco::EcoOpening,
     game::{self, Game},
+    game_database::{GameDatabase, GameFilter, MoveStats},
     moves::Move,
     piece::{Piece, PieceType},
+    read_fen_string::piece_to_alg,
+    search::{SearchLimits, SearchResult, is_mate_score},
+    variant::VARIANTS,
 };
+use editor::BoardEditor;
 use macroquad::prelude::*;
-use std::{cmp, collections::HashMap};
+use side_panel::{HistoryView, SIDE_PANEL_WIDTH};
+use std::sync::mpsc::{self, Receiver};
+use std::{collections::HashMap, io, path::Path, time::Duration};
+
+mod assets;
+mod editor;
+mod highlights;
+mod side_panel;
+mod theme;
+
+pub use theme::{BOARD_SCHEMES, BoardScheme, Theme, board_scheme};
 
 pub struct GUIDisplayer {
     rows: usize,
@@ -16,10 +33,75 @@ pub struct GUIDisplayer {
     offset_x: f32,
     offset_y: f32,
     selected_piece_square: Option<(usize, usize)>,
+    // Black at the bottom of the board instead of White
+    flipped: bool,
+    // The side played at this display, whose pieces can be premoved on the opponent's turn
+    player_color: game::Color,
+    // From and to squares of the move to play as soon as it is the player's turn
+    premove: Option<((usize, usize), (usize, usize))>,
+    theme: Theme,
+    // The selected piece is held by the mouse and follows the cursor until it is let go
+    dragging: bool,
+    // Moves to the same square that differ in what the pawn promotes to, while the player picks one
+    promotion_choices: Option<Vec<Move>>,
     background_tex: Texture2D,
     piece_texs: HashMap<(PieceType, game::Color), Texture2D>,
+    explorer: Option<GameDatabase>,
+    show_explorer: bool,
+    // Position hash and the moves played from it, so the database isn't read every frame
+    explorer_moves: Option<(u64, Vec<MoveStats>)>,
+    // Position hash and moves played when the opening was last classified
+    opening: Option<(u64, usize, Option<&'static EcoOpening>)>,
+    // Crazyhouse pockets are shown in two columns right of the board
+    show_pockets: bool,
+    selected_drop: Option<PieceType>,
+    // Ply and start time of the Atomic explosion being animated
+    explosion_animation: Option<(usize, f64)>,
+    // Set while a position is being set up, which replaces the game on screen
+    editor: Option<BoardEditor>,
+    // Show the engine's evaluation and best move for every position
+    analysis: bool,
+    analysis_result: Option<Analysis>,
+    // Latest messages from the game, shown above the move list
+    messages: Vec<String>,
+    // Position hash and moves played, and the SAN of every move
+    move_list: Option<((u64, usize), Vec<String>)>,
+    // Set while looking back at an earlier position of the game
+    history_view: Option<HistoryView>,
+    // N or V pressed once during a game, waiting for a second press to abandon it
+    new_game_key: Option<KeyCode>,
+}
+
+// A search of the position with this hash, running on a worker thread so the window
+// keeps drawing frames meanwhile
+struct Analysis {
+    hash: u64,
+    receiver: Receiver<SearchResult>,
+    result: Option<SearchResult>,
 }
 
+const SAVED_PGN_PATH: &str = "game.pgn";
+
+const POCKET_COLUMNS: usize = 2;
+
+// The order promotion choices are offered in, most popular first. Fairy pieces and
+// Antichess kings come after these, in the order the variant gives them.
+const PROMOTION_ORDER: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+const EXPLOSION_SECONDS: f64 = 0.6;
+
+// Thinking time per position in analysis, short enough to keep up with moves being played
+const ANALYSIS_TIME: Duration = Duration::from_millis(300);
+
+// Share of the window width taken by the opening explorer
+const EXPLORER_WIDTH: f32 = 0.35;
+const EXPLORER_MAX_ROWS: usize = 20;
+
 const T_ORANGE: Color = Color {
     r: 1.,
     g: 0.63,
@@ -34,55 +116,238 @@ const T_GREY: Color = Color {
     a: 0.5,
 };
 
-const OLIVE: Color = Color {
-    r: 119. / 255.,
-    g: 149. / 255.,
-    b: 86. / 255.,
-    a: 1.,
+const T_GOLD: Color = Color {
+    r: 1.,
+    g: 0.84,
+    b: 0.,
+    a: 0.4,
+};
+
+const T_BLUE: Color = Color {
+    r: 0.2,
+    g: 0.4,
+    b: 1.,
+    a: 0.4,
 };
 
 impl Display for GUIDisplayer {
-    fn display_message(&self, message: String) {}
+    fn display_message(&mut self, message: String) {
+        self.add_message(message);
+    }
 
     async fn display(&mut self, game: &Game) {
         if (self.sw != screen_width()) || (self.sh != screen_height()) {
             (self.sw, self.sh) = (screen_width(), screen_height());
             self.recalculate();
         }
+        if self.editor.is_some() {
+            self.draw_background();
+            self.draw_editor();
+            next_frame().await;
+            return;
+        }
+        if (self.rows, self.cols) != (game.rows, game.cols) {
+            (self.rows, self.cols) = (game.rows, game.cols);
+            self.selected_piece_square = None;
+            self.recalculate();
+        }
+        if self.show_pockets != game.variant.has_pockets() {
+            self.show_pockets = game.variant.has_pockets();
+            self.selected_drop = None;
+            self.recalculate();
+        }
+
+        // Everything but the move list shows the position being looked at
+        self.sync_history_view(game);
+        let history_view = self.history_view.take();
+        let shown = history_view.as_ref().map_or(game, |view| &view.position);
 
         self.draw_background();
 
         self.draw_board();
 
-        self.draw_board_pieces(game);
+        self.draw_last_move(shown);
+
+        if history_view.is_none() {
+            self.draw_premove();
+        }
+
+        self.draw_goal_squares(shown);
+
+        self.draw_analysis(shown);
+
+        self.draw_check(shown);
+
+        self.draw_board_pieces(shown);
+
+        self.draw_pockets(shown);
 
-        self.piece_selection(game);
+        self.draw_explosion(shown);
 
-        self.draw_piece_selection(game);
+        self.draw_explorer(shown);
+
+        self.draw_side_panel(game, shown);
+
+        self.draw_piece_selection(shown);
+
+        self.draw_promotion_choices(shown);
+
+        self.history_view = history_view;
 
         next_frame().await;
     }
 
-    fn user_input(&mut self, game: &Game) -> Option<Move> {
+    async fn confirm_takeback(&mut self, game: &Game, color: game::Color) -> bool {
+        let prompt = format!("{}: accept takeback? (Y/N)", color);
+        loop {
+            if is_key_pressed(KeyCode::Y) {
+                return true;
+            }
+            if is_key_pressed(KeyCode::N) {
+                return false;
+            }
+
+            self.draw_background();
+            self.draw_board();
+            self.draw_board_pieces(game);
+
+            let font_size = self.square_size / 2.;
+            let dims = measure_text(&prompt, None, font_size as u16, 1.);
+            let (x, y) = ((self.sw - dims.width) / 2., (self.sh - dims.height) / 2.);
+            draw_rectangle(
+                x - font_size / 2.,
+                y - font_size / 2.,
+                dims.width + font_size,
+                dims.height + font_size,
+                T_GREY,
+            );
+            draw_text(&prompt, x, y + dims.offset_y, font_size, WHITE);
+
+            next_frame().await;
+        }
+    }
+
+    fn set_player_color(&mut self, color: game::Color) {
+        self.player_color = color;
+        self.flipped = color == game::Color::Black;
+    }
+
+    fn user_input(&mut self, game: &Game) -> Option<UserAction> {
+        if self.editor.is_some() {
+            return self.editor_input();
+        }
+        if self.promotion_choices.is_some() {
+            return self.promotion_input();
+        }
+        self.navigation_input(game);
+        if let Some(action) = self.play_premove(game) {
+            return Some(action);
+        }
+        if self.premove.is_some()
+            && (is_key_pressed(KeyCode::Escape) || is_mouse_button_pressed(MouseButton::Right))
+        {
+            self.premove = None;
+        }
+        if [KeyCode::B, KeyCode::U, KeyCode::R, KeyCode::N, KeyCode::V]
+            .iter()
+            .any(|&key| is_key_pressed(key))
+        {
+            // The premove was meant for the position on the board
+            self.premove = None;
+        }
+        if is_key_pressed(KeyCode::B) {
+            self.open_editor(game);
+            return None;
+        }
+        if is_key_pressed(KeyCode::A) {
+            self.analysis = !self.analysis;
+        }
+        if is_key_pressed(KeyCode::F) {
+            self.flipped = !self.flipped;
+        }
+        if is_key_pressed(KeyCode::T) {
+            let current = BOARD_SCHEMES
+                .iter()
+                .position(|scheme| scheme.name == self.theme.board.name)
+                .unwrap_or(0);
+            self.theme.board = BOARD_SCHEMES[(current + 1) % BOARD_SCHEMES.len()];
+            self.add_message(format!("Board colours: {}", self.theme.board.name));
+        }
+        if is_key_pressed(KeyCode::U) {
+            self.selected_piece_square = None;
+            return Some(UserAction::Undo);
+        }
+        if is_key_pressed(KeyCode::R) {
+            self.selected_piece_square = None;
+            return Some(UserAction::Redo);
+        }
+        if is_key_pressed(KeyCode::S) {
+            return Some(UserAction::SavePgn(SAVED_PGN_PATH.to_string()));
+        }
+        if let Some(action) = self.new_game_input(game) {
+            return Some(action);
+        }
+        if is_key_pressed(KeyCode::E) && self.explorer.is_some() {
+            self.show_explorer = !self.show_explorer;
+            self.recalculate();
+        }
+        // Earlier positions are only there to look at
+        if self.history_view.is_some() {
+            return None;
+        }
+
+        if is_mouse_button_pressed(MouseButton::Left)
+            && let Some(mv) = self.explorer_row_under_mouse().map(|stats| stats.mv)
+        {
+            self.selected_piece_square = None;
+            return Some(UserAction::Move(mv));
+        }
+
+        if is_mouse_button_pressed(MouseButton::Left)
+            && let Some((color, piece_type)) = self.pocket_piece_under_mouse()
+        {
+            // Pick a piece up from the side to move's pocket
+            self.selected_piece_square = None;
+            self.selected_drop = (color == game.next_player
+                && game.pocket(color).count(piece_type) > 0)
+                .then_some(piece_type);
+            return None;
+        }
+
+        if is_mouse_button_pressed(MouseButton::Left)
+            && let Some(piece_type) = self.selected_drop.take()
+            && let Some(to_position) = self.get_mouse_position_pair()
+            && let Some(mv) = game.next_legal_moves.iter().find(|mv| {
+                matches!(mv, Move::Drop(drop_mv)
+                    if drop_mv.piece.piece_type == piece_type && drop_mv.to_position == to_position)
+            })
+        {
+            return Some(UserAction::Move(*mv));
+        }
+
         if is_mouse_button_pressed(MouseButton::Left) {
-            match self.selected_piece_square {
-                None => {
-                    self.try_to_select(game);
-                }
-                Some((i_sel, j_sel)) => {
-                    if let Some((i_cand, j_cand)) = self.get_mouse_position_pair() {
-                        if let Some(mv) = game
-                            .moves_from_square((i_sel, j_sel))
-                            .iter()
-                            .find(|mv| mv.get_to_position() == (i_cand, j_cand))
-                        {
-                            self.selected_piece_square = None;
-                            return Some(*mv);
-                        }
-
-                        self.try_to_select(game);
-                    }
-                }
+            // Click the piece, then where it goes, or start dragging it
+            if let Some(from) = self.selected_piece_square
+                && let Some(to) = self.get_mouse_position_pair()
+                && to != from
+                && self
+                    .selection_targets(game, from)
+                    .iter()
+                    .any(|&(target, _)| target == to)
+            {
+                return self.move_selected_piece(game, to);
+            }
+            self.try_to_select(game);
+            self.dragging = self.selected_piece_square.is_some();
+        }
+
+        if self.dragging && is_mouse_button_released(MouseButton::Left) {
+            // Dropped back where it started, it stays selected for a click on the target
+            self.dragging = false;
+            if let Some(to) = self.get_mouse_position_pair()
+                && Some(to) != self.selected_piece_square
+            {
+                return self.move_selected_piece(game, to);
             }
         }
         return None;
@@ -90,12 +355,7 @@ impl Display for GUIDisplayer {
 }
 
 impl GUIDisplayer {
-    pub async fn new(rows: usize, cols: usize) -> Self {
-        let background_tex = load_texture("./assets/background.png").await.unwrap();
-        let mut piece_text_map: HashMap<(PieceType, game::Color), Texture2D> = HashMap::new();
-
-        import_pieces(&mut piece_text_map).await;
-
+    pub fn new(rows: usize, cols: usize) -> Self {
         GUIDisplayer {
             rows,
             cols,
@@ -105,19 +365,411 @@ impl GUIDisplayer {
             offset_x: 0.,
             offset_y: 0.,
             selected_piece_square: None,
-            background_tex: background_tex,
-            piece_texs: piece_text_map,
+            flipped: false,
+            player_color: game::Color::White,
+            premove: None,
+            theme: Theme::default(),
+            dragging: false,
+            promotion_choices: None,
+            background_tex: assets::default_background(),
+            piece_texs: assets::default_pieces(),
+            explorer: None,
+            show_explorer: false,
+            explorer_moves: None,
+            opening: None,
+            show_pockets: false,
+            selected_drop: None,
+            explosion_animation: None,
+            editor: None,
+            analysis: false,
+            analysis_result: None,
+            messages: Vec::new(),
+            move_list: None,
+            history_view: None,
+            new_game_key: None,
+        }
+    }
+
+    // N starts a Chess960 game and V a game of the next variant. Once moves have been played
+    // the key has to be pressed twice, so a game isn't thrown away by a stray key press.
+    fn new_game_input(&mut self, game: &Game) -> Option<UserAction> {
+        let pressed = get_keys_pressed();
+        let key = [KeyCode::N, KeyCode::V]
+            .into_iter()
+            .find(|key| pressed.contains(key));
+        let Some(key) = key else {
+            if !pressed.is_empty() {
+                self.new_game_key = None;
+            }
+            return None;
+        };
+        if !game.move_history.is_empty() && self.new_game_key != Some(key) {
+            self.new_game_key = Some(key);
+            self.add_message(format!("Press {:?} again to abandon this game", key));
+            return None;
+        }
+
+        self.new_game_key = None;
+        self.selected_piece_square = None;
+        if key == KeyCode::N {
+            return Some(UserAction::NewChess960Game);
+        }
+        // Cycle through the variants, starting a new game of the next one
+        let current = VARIANTS
+            .iter()
+            .position(|variant| variant.name() == game.variant.name())
+            .unwrap_or(0);
+        let next = VARIANTS[(current + 1) % VARIANTS.len()];
+        Some(UserAction::NewGame(next.name().to_string()))
+    }
+
+    fn opening_name(&mut self, game: &Game) -> Option<String> {
+        let key = (game.position_hash(), game.move_history.len());
+        let opening = match self.opening {
+            Some((hash, moves, opening)) if (hash, moves) == key => opening,
+            _ => {
+                let opening = game.eco_opening();
+                self.opening = Some((key.0, key.1, opening));
+                opening
+            }
+        };
+        opening.map(|opening| format!("{} {}", opening.eco, opening.name))
+    }
+
+    // A flash spreading out from the capture square after a move explodes
+    fn draw_explosion(&mut self, game: &Game) {
+        let Some(explosion) = game.last_explosion() else {
+            return;
+        };
+        let start = match self.explosion_animation {
+            Some((ply, start)) if ply == explosion.ply => start,
+            _ => {
+                let start = get_time();
+                self.explosion_animation = Some((explosion.ply, start));
+                start
+            }
+        };
+        let progress = ((get_time() - start) / EXPLOSION_SECONDS) as f32;
+        if progress >= 1. {
+            return;
+        }
+
+        let (x, y) = self.board_square_pixels(explosion.center.0, explosion.center.1);
+        let (cx, cy) = (x + self.square_size / 2., y + self.square_size / 2.);
+        let fade = 1. - progress;
+        draw_circle(
+            cx,
+            cy,
+            self.square_size * 1.5 * progress,
+            Color::new(1., 0.5, 0., 0.7 * fade),
+        );
+        draw_circle(
+            cx,
+            cy,
+            self.square_size * 0.8 * progress,
+            Color::new(1., 0.9, 0.3, 0.9 * fade),
+        );
+        // The pieces that were blown up fade out where they stood
+        for &((i, j), piece) in &explosion.destroyed {
+            let (px, py) = self.board_square_pixels(i, j);
+            // Like on the board, pieces without an image are a lettered disc
+            let Some(text) = self.piece_texs.get(&(piece.piece_type, piece.color)) else {
+                self.draw_lettered_piece(&piece, px, py);
+                continue;
+            };
+            draw_texture_ex(
+                text,
+                px,
+                py,
+                Color::new(1., 1., 1., fade),
+                DrawTextureParams {
+                    dest_size: Some(Vec2 {
+                        x: self.square_size,
+                        y: self.square_size,
+                    }),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    // Marks the engine's best move on the board and writes its evaluation, from White's side.
+    // A new position is searched in the background, and shows nothing until that is done.
+    fn draw_analysis(&mut self, game: &Game) {
+        if !self.analysis || game.state.is_over() {
+            return;
+        }
+        let hash = game.position_hash();
+        if self
+            .analysis_result
+            .as_ref()
+            .is_none_or(|analysis| analysis.hash != hash)
+        {
+            // A search of the previous position finishes on its own and its result is dropped
+            let (sender, receiver) = mpsc::channel();
+            let position = game.clone();
+            rayon::spawn(move || {
+                let result = position.search(SearchLimits {
+                    depth: None,
+                    time: Some(ANALYSIS_TIME),
+                });
+                let _ = sender.send(result);
+            });
+            self.analysis_result = Some(Analysis {
+                hash,
+                receiver,
+                result: None,
+            });
+        }
+        if let Some(analysis) = self.analysis_result.as_mut()
+            && let Ok(result) = analysis.receiver.try_recv()
+        {
+            analysis.result = Some(result);
+        }
+        let Some(result) = self
+            .analysis_result
+            .as_ref()
+            .and_then(|analysis| analysis.result.as_ref())
+        else {
+            return;
+        };
+
+        let score = match game.next_player {
+            game::Color::White => result.score,
+            game::Color::Black => -result.score,
+        };
+        let evaluation = if is_mate_score(score) {
+            let winner = if score > 0 { "White" } else { "Black" };
+            format!("{} mates", winner)
+        } else {
+            format!("{:+.2}", score as f32 / 100.)
+        };
+        let best_move = result.best_move.and_then(|mv| game.move_to_san(mv));
+        let text = match best_move {
+            Some(san) => format!("{}, best {} (depth {})", evaluation, san, result.depth),
+            None => evaluation,
+        };
+
+        if let Some(mv) = result.best_move {
+            for (i, j) in [mv.get_from_position(), mv.get_to_position()] {
+                self.highlight_square(i, j, T_BLUE);
+            }
+        }
+        let font_size = self.square_size / 4.;
+        let dims = measure_text(&text, None, font_size as u16, 1.);
+        let padding = font_size / 4.;
+        let (x, y) = (
+            self.sw - dims.width - padding * 2.,
+            self.sh - dims.height - padding * 2.,
+        );
+        draw_rectangle(
+            x,
+            y,
+            dims.width + padding * 2.,
+            dims.height + padding * 2.,
+            T_GREY,
+        );
+        draw_text(
+            &text,
+            x + padding,
+            y + padding + dims.offset_y,
+            font_size,
+            WHITE,
+        );
+    }
+
+    fn draw_goal_squares(&self, game: &Game) {
+        for (i, j) in game.variant.goal_squares() {
+            self.highlight_square(i, j, T_GOLD);
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    // Use the piece set in `dir` instead of the built in one, keeping the current one if
+    // the set can't be read
+    pub fn set_piece_set(&mut self, dir: &Path) -> Result<(), io::Error> {
+        self.piece_texs = assets::load_piece_set(dir)?;
+        Ok(())
+    }
+
+    // Show the moves played from each position in the database's games next to the board
+    pub fn set_explorer(&mut self, database: GameDatabase) {
+        self.explorer = Some(database);
+        self.show_explorer = true;
+        self.explorer_moves = None;
+        self.recalculate();
+    }
+
+    fn explorer_visible(&self) -> bool {
+        self.explorer.is_some() && self.show_explorer
+    }
+
+    fn update_explorer_moves(&mut self, game: &Game) {
+        let Some(database) = &self.explorer else {
+            return;
+        };
+        let hash = game.position_hash();
+        if self
+            .explorer_moves
+            .as_ref()
+            .is_some_and(|(cached, _)| *cached == hash)
+        {
+            return;
+        }
+        let moves = database
+            .move_stats(game, &GameFilter::default())
+            .unwrap_or_default();
+        self.explorer_moves = Some((hash, moves));
+    }
+
+    // Left edge, top edge, width and row height of the explorer panel
+    fn explorer_layout(&self) -> (f32, f32, f32, f32) {
+        let (board_left, board_width) = self.board_area();
+        let x = board_left + board_width;
+        let row_height = (self.sh / (EXPLORER_MAX_ROWS + 3) as f32).min(self.square_size / 2.);
+        (x, self.offset_y, self.sw - x - row_height / 2., row_height)
+    }
+
+    fn explorer_row_under_mouse(&self) -> Option<&MoveStats> {
+        if !self.explorer_visible() {
+            return None;
+        }
+        let (x, y, width, row_height) = self.explorer_layout();
+        let (mx, my) = mouse_position();
+        // The first row is the header
+        let first_row = y + row_height * 2.;
+        if mx < x || mx >= x + width || my < first_row {
+            return None;
+        }
+        let row = ((my - first_row) / row_height) as usize;
+        if row >= EXPLORER_MAX_ROWS {
+            return None;
+        }
+        self.explorer_moves.as_ref()?.1.get(row)
+    }
+
+    fn draw_explorer(&mut self, game: &Game) {
+        if !self.explorer_visible() {
+            return;
+        }
+        self.update_explorer_moves(game);
+
+        let (x, y, width, row_height) = self.explorer_layout();
+        let font_size = row_height * 0.8;
+        let moves = self
+            .explorer_moves
+            .as_ref()
+            .map_or(&[][..], |(_, moves)| moves.as_slice());
+        let rows = moves.len().clamp(1, EXPLORER_MAX_ROWS);
+        draw_rectangle(x, y, width, row_height * (rows + 2) as f32, T_GREY);
+
+        let text_y = |row: usize| y + row_height * (row as f32 + 0.8);
+        draw_text("Move", x + 4., text_y(0), font_size, WHITE);
+        draw_text("Games", x + width * 0.2, text_y(0), font_size, WHITE);
+        draw_text(
+            "White / Draw / Black",
+            x + width * 0.45,
+            text_y(0),
+            font_size,
+            WHITE,
+        );
+        if moves.is_empty() {
+            draw_text("No games", x + 4., text_y(2), font_size, WHITE);
+            return;
+        }
+
+        let hovered = self.explorer_row_under_mouse().map(|stats| stats.mv);
+        for (row, stats) in moves.iter().take(EXPLORER_MAX_ROWS).enumerate() {
+            let row_y = y + row_height * (row + 2) as f32;
+            if hovered == Some(stats.mv) {
+                draw_rectangle(x, row_y, width, row_height, T_ORANGE);
+            }
+            draw_text(&stats.san, x + 4., text_y(row + 2), font_size, WHITE);
+            draw_text(
+                &stats.games.to_string(),
+                x + width * 0.2,
+                text_y(row + 2),
+                font_size,
+                WHITE,
+            );
+
+            // Results bar split into white wins, draws and black wins
+            let (bar_x, bar_width) = (x + width * 0.45, width * 0.5);
+            let (bar_y, bar_height) = (row_y + row_height * 0.15, row_height * 0.7);
+            let mut segment_x = bar_x;
+            for (count, color) in [
+                (stats.white_wins, WHITE),
+                (stats.draws, GRAY),
+                (stats.black_wins, BLACK),
+            ] {
+                let share = count as f32 / stats.games as f32;
+                let segment_width = bar_width * share;
+                draw_rectangle(segment_x, bar_y, segment_width, bar_height, color);
+                if share >= 0.15 {
+                    let text_color = if color == WHITE { BLACK } else { WHITE };
+                    draw_text(
+                        &format!("{:.0}%", share * 100.),
+                        segment_x + 2.,
+                        bar_y + bar_height * 0.8,
+                        font_size * 0.8,
+                        text_color,
+                    );
+                }
+                segment_x += segment_width;
+            }
         }
     }
 
     fn draw_board(&self) {
         for i in 0..self.rows {
             for j in 0..self.cols {
-                let color = if (i + j) % 2 == 0 { OLIVE } else { WHITE };
                 let (x, y) = self.board_square_pixels(i, j);
-                draw_rectangle(x, y, self.square_size, self.square_size, color);
+                draw_rectangle(
+                    x,
+                    y,
+                    self.square_size,
+                    self.square_size,
+                    self.square_color(i, j),
+                );
             }
         }
+        self.draw_coordinates();
+    }
+
+    // Files along the bottom edge and ranks along the left one, inside the squares
+    fn draw_coordinates(&self) {
+        let font_size = self.square_size / 5.;
+        let padding = font_size / 4.;
+        let (bottom_row, left_col) = if self.flipped {
+            (self.rows - 1, self.cols - 1)
+        } else {
+            (0, 0)
+        };
+        for j in 0..self.cols {
+            let file = ((b'a' + j as u8) as char).to_string();
+            let dims = measure_text(&file, None, font_size as u16, 1.);
+            let (x, y) = self.board_square_pixels(bottom_row, j);
+            draw_text(
+                &file,
+                x + self.square_size - dims.width - padding,
+                y + self.square_size - padding,
+                font_size,
+                self.square_color(bottom_row + 1, j),
+            );
+        }
+        for i in 0..self.rows {
+            let (x, y) = self.board_square_pixels(i, left_col);
+            draw_text(
+                &(i + 1).to_string(),
+                x + padding,
+                y + padding + font_size * 0.7,
+                font_size,
+                self.square_color(i, left_col + 1),
+            );
+        }
     }
 
     fn draw_background(&self) {
@@ -155,10 +807,10 @@ impl GUIDisplayer {
     }
 
     fn draw_piece(&self, piece: &Piece, x: f32, y: f32) {
-        let text = self
-            .piece_texs
-            .get(&(piece.piece_type, piece.color))
-            .expect("Piece not loaded");
+        let Some(text) = self.piece_texs.get(&(piece.piece_type, piece.color)) else {
+            self.draw_lettered_piece(piece, x, y);
+            return;
+        };
         draw_texture_ex(
             text,
             x,
@@ -174,61 +826,270 @@ impl GUIDisplayer {
         );
     }
 
+    // Fairy pieces have no images, so they are drawn as a disc with their FEN letter
+    fn draw_lettered_piece(&self, piece: &Piece, x: f32, y: f32) {
+        let (fill, outline) = match piece.color {
+            game::Color::White => (WHITE, BLACK),
+            game::Color::Black => (BLACK, WHITE),
+        };
+        let (centre_x, centre_y) = (x + self.square_size / 2., y + self.square_size / 2.);
+        draw_circle(centre_x, centre_y, self.square_size * 0.35, outline);
+        draw_circle(centre_x, centre_y, self.square_size * 0.32, fill);
+
+        let letter = piece_to_alg(*piece).to_ascii_uppercase().to_string();
+        let font_size = self.square_size * 0.5;
+        let dims = measure_text(&letter, None, font_size as u16, 1.);
+        draw_text(
+            &letter,
+            centre_x - dims.width / 2.,
+            centre_y + dims.offset_y / 2.,
+            font_size,
+            outline,
+        );
+    }
+
+    // Left column for the pocket of the side at the bottom, filled from the bottom, and
+    // right column for the other side's from the top
+    fn pocket_square_pixels(&self, color: game::Color, index: usize) -> (f32, f32) {
+        if (color == game::Color::White) != self.flipped {
+            self.layout_square_pixels(self.rows - 1 - index, self.cols)
+        } else {
+            self.layout_square_pixels(index, self.cols + 1)
+        }
+    }
+
+    fn pocket_piece_under_mouse(&self) -> Option<(game::Color, PieceType)> {
+        if !self.show_pockets {
+            return None;
+        }
+        let (mx, my) = mouse_position();
+        for color in [game::Color::White, game::Color::Black] {
+            for (index, &piece_type) in POCKET_PIECES.iter().enumerate() {
+                let (x, y) = self.pocket_square_pixels(color, index);
+                if (x <= mx && mx < x + self.square_size) && (y <= my && my < y + self.square_size)
+                {
+                    return Some((color, piece_type));
+                }
+            }
+        }
+        None
+    }
+
+    fn draw_pockets(&self, game: &Game) {
+        if !self.show_pockets {
+            return;
+        }
+        let font_size = self.square_size / 3.;
+        for color in [game::Color::White, game::Color::Black] {
+            for (index, &piece_type) in POCKET_PIECES.iter().enumerate() {
+                let (x, y) = self.pocket_square_pixels(color, index);
+                let selected = color == game.next_player && self.selected_drop == Some(piece_type);
+                let background = if selected { T_ORANGE } else { T_GREY };
+                draw_rectangle(x, y, self.square_size, self.square_size, background);
+
+                let count = game.pocket(color).count(piece_type);
+                if count == 0 {
+                    continue;
+                }
+                let piece = Piece {
+                    color,
+                    piece_type,
+                    has_moved: true,
+                    promoted: false,
+                };
+                self.draw_piece(&piece, x, y);
+                draw_text(
+                    &count.to_string(),
+                    x + self.square_size - font_size * 0.6,
+                    y + self.square_size - font_size * 0.2,
+                    font_size,
+                    WHITE,
+                );
+            }
+        }
+    }
+
     fn draw_piece_selection(&self, game: &Game) {
+        if let Some(piece_type) = self.selected_drop {
+            for mv in &game.next_legal_moves {
+                if let Move::Drop(drop_mv) = mv
+                    && drop_mv.piece.piece_type == piece_type
+                {
+                    self.draw_move_target(drop_mv.to_position, false);
+                }
+            }
+        }
+
         if let Some((i, j)) = self.selected_piece_square {
-            self.highlight_square(i, j, T_ORANGE);
+            self.highlight_square(i, j, self.theme.selected);
 
-            // draw selected piece
-            let (x, y) = self.board_square_pixels(i, j);
+            // draw selected piece, under the cursor while it is dragged
+            let (x, y) = if self.dragging {
+                let (mx, my) = mouse_position();
+                (mx - self.square_size / 2., my - self.square_size / 2.)
+            } else {
+                self.board_square_pixels(i, j)
+            };
 
             self.draw_piece(&game.board[i][j].expect(""), x, y);
 
-            for mv in game.moves_from_square((i, j)) {
-                let to_postion = mv.get_to_position();
-                let (x, y) = self.board_square_pixels(to_postion.0, to_postion.1);
-                draw_circle(
-                    x + self.square_size / 2.,
-                    y + self.square_size / 2.,
-                    self.square_size / 6.,
-                    T_GREY,
-                )
+            for (to_position, capture) in self.selection_targets(game, (i, j)) {
+                self.draw_move_target(to_position, capture);
             }
         }
     }
 
     fn try_to_select(&mut self, game: &Game) {
-        if let Some((i_cand, j_cand)) = self.get_mouse_position_pair() {
-            if !game.moves_from_square((i_cand, j_cand)).is_empty() {
-                self.selected_piece_square = Some((i_cand, j_cand));
+        self.selected_piece_square = self
+            .get_mouse_position_pair()
+            .filter(|&square| !self.selection_targets(game, square).is_empty());
+    }
+
+    // Play the selected piece's move to the square, asking first what a pawn promotes to
+    fn move_selected_piece(&mut self, game: &Game, to: (usize, usize)) -> Option<UserAction> {
+        let from = self.selected_piece_square?;
+        if game.board[from.0][from.1].is_some_and(|piece| piece.color != game.next_player) {
+            self.set_premove(game, to);
+            return None;
+        }
+        let mut moves: Vec<Move> = game
+            .moves_from_square(from)
+            .into_iter()
+            .filter(|mv| mv.get_to_position() == to)
+            .collect();
+        match moves.len() {
+            0 => None,
+            1 => {
+                self.selected_piece_square = None;
+                Some(UserAction::Move(moves[0]))
+            }
+            _ => {
+                moves.sort_by_key(|mv| promotion_order(*mv));
+                self.promotion_choices = Some(moves);
+                None
+            }
+        }
+    }
+
+    // A column of choices from the promotion square towards the middle of the board,
+    // carrying on in the next column over if it runs out of rows
+    fn promotion_choice_squares(&self, to: (usize, usize), count: usize) -> Vec<(usize, usize)> {
+        let row_dir = if to.0 < self.rows / 2 { 1 } else { -1 };
+        let col_dir = if to.1 < self.cols / 2 { 1 } else { -1 };
+        (0..count)
+            .map(|index| {
+                let (down, across) = ((index % self.rows) as i32, (index / self.rows) as i32);
+                (
+                    (to.0 as i32 + row_dir * down) as usize,
+                    (to.1 as i32 + col_dir * across) as usize,
+                )
+            })
+            .collect()
+    }
+
+    fn promotion_input(&mut self) -> Option<UserAction> {
+        let choices = self.promotion_choices.as_ref()?;
+        if is_key_pressed(KeyCode::Escape) {
+            self.promotion_choices = None;
+            return None;
+        }
+        if !is_mouse_button_pressed(MouseButton::Left) {
+            return None;
+        }
+        // Clicking anywhere but a choice takes the move back
+        let squares = self.promotion_choice_squares(choices[0].get_to_position(), choices.len());
+        let chosen = self
+            .get_mouse_position_pair()
+            .and_then(|square| squares.iter().position(|&choice| choice == square))
+            .map(|index| choices[index]);
+        self.promotion_choices = None;
+        if chosen.is_some() {
+            self.selected_piece_square = None;
+        }
+        chosen.map(UserAction::Move)
+    }
+
+    fn draw_promotion_choices(&self, game: &Game) {
+        let Some(choices) = &self.promotion_choices else {
+            return;
+        };
+        let board_width = self.square_size * self.cols as f32;
+        let board_height = self.square_size * self.rows as f32;
+        draw_rectangle(
+            self.offset_x,
+            self.offset_y,
+            board_width,
+            board_height,
+            T_GREY,
+        );
+
+        let squares = self.promotion_choice_squares(choices[0].get_to_position(), choices.len());
+        for (mv, (i, j)) in choices.iter().zip(squares) {
+            let piece = match mv {
+                Move::Promotion(mv) => mv.new_piece,
+                // Staying a pawn, where a variant allows it
+                _ => game.board[mv.get_from_position().0][mv.get_from_position().1]
+                    .expect("Promoting pawn is on its square"),
+            };
+            let (x, y) = self.board_square_pixels(i, j);
+            let (centre_x, centre_y) = (x + self.square_size / 2., y + self.square_size / 2.);
+            draw_circle(centre_x, centre_y, self.square_size / 2., WHITE);
+            if self.get_mouse_position_pair() == Some((i, j)) {
+                draw_circle(centre_x, centre_y, self.square_size / 2., T_ORANGE);
             }
+            self.draw_piece(&piece, x, y);
         }
     }
 
     fn recalculate(&mut self) {
-        self.square_size = (self.sw / self.cols as f32).min((self.sh / self.rows as f32));
+        let (board_left, board_width) = self.board_area();
+        self.square_size =
+            (board_width / self.layout_cols() as f32).min((self.sh / self.rows as f32));
 
         (self.offset_x, self.offset_y) = (
-            (self.sw - self.square_size * self.cols as f32) / 2.,
+            board_left + (board_width - self.square_size * self.layout_cols() as f32) / 2.,
             (self.sh - self.square_size * self.rows as f32) / 2.,
         );
     }
 
-    fn highlight_square(&self, i: usize, j: usize, color: macroquad::color::Color) {
-        let (x, y) = self.board_square_pixels(i, j);
-        draw_rectangle(x, y, self.square_size, self.square_size, color);
+    // Left edge and width of the space for the board, between the side panel and the
+    // explorer when they are shown
+    fn board_area(&self) -> (f32, f32) {
+        let left = if self.editor.is_some() {
+            0.
+        } else {
+            self.sw * SIDE_PANEL_WIDTH
+        };
+        let right = if self.explorer_visible() {
+            self.sw * EXPLORER_WIDTH
+        } else {
+            0.
+        };
+        (left, self.sw - left - right)
     }
 
-    fn piece_selection(&mut self, game: &Game) {
-        if is_mouse_button_pressed(MouseButton::Left) && self.selected_piece_square.is_none() {
-            if let Some((i_cand, j_cand)) = self.get_mouse_position_pair() {
-                if !game.moves_from_square((i_cand, j_cand)).is_empty() {
-                    self.selected_piece_square = Some((i_cand, j_cand));
-                    return;
-                }
-            }
+    // Columns of squares laid out side by side, the board and any pockets or editor palette
+    fn layout_cols(&self) -> usize {
+        if self.show_pockets || self.editor.is_some() {
+            self.cols + POCKET_COLUMNS
+        } else {
+            self.cols
+        }
+    }
+
+    fn square_color(&self, i: usize, j: usize) -> Color {
+        if (i + j).is_multiple_of(2) {
+            self.theme.board.dark
+        } else {
+            self.theme.board.light
         }
     }
 
+    fn highlight_square(&self, i: usize, j: usize, color: macroquad::color::Color) {
+        let (x, y) = self.board_square_pixels(i, j);
+        draw_rectangle(x, y, self.square_size, self.square_size, color);
+    }
+
     fn get_mouse_position_pair(&self) -> Option<(usize, usize)> {
         let (mx, my) = mouse_position();
         for i in 0..self.rows {
@@ -245,9 +1106,19 @@ impl GUIDisplayer {
     }
 
     fn board_square_pixels(&self, i: usize, j: usize) -> (f32, f32) {
+        if self.flipped {
+            self.layout_square_pixels(i, self.cols - j - 1)
+        } else {
+            self.layout_square_pixels(self.rows - i - 1, j)
+        }
+    }
+
+    // Top left corner of a square of the layout, counting rows from the top and columns
+    // from the left, whichever way round the board is
+    fn layout_square_pixels(&self, row: usize, col: usize) -> (f32, f32) {
         (
-            self.offset_x + j as f32 * self.square_size,
-            self.offset_y + (self.rows - i - 1) as f32 * self.square_size,
+            self.offset_x + col as f32 * self.square_size,
+            self.offset_y + row as f32 * self.square_size,
         )
     }
 
@@ -266,70 +1137,12 @@ impl GUIDisplayer {
     }
 }
 
-async fn import_pieces(map: &mut HashMap<(PieceType, game::Color), Texture2D>) {
-    let pieces = [
-        (
-            PieceType::Pawn,
-            game::Color::Black,
-            "./assets/pawn_black.png",
-        ),
-        (
-            PieceType::Rook,
-            game::Color::Black,
-            "./assets/rook_black.png",
-        ),
-        (
-            PieceType::Bishop,
-            game::Color::Black,
-            "./assets/bishop_black.png",
-        ),
-        (
-            PieceType::Knight,
-            game::Color::Black,
-            "./assets/knight_black.png",
-        ),
-        (
-            PieceType::Queen,
-            game::Color::Black,
-            "./assets/queen_black.png",
-        ),
-        (
-            PieceType::King,
-            game::Color::Black,
-            "./assets/king_black.png",
-        ),
-        (
-            PieceType::Pawn,
-            game::Color::White,
-            "./assets/pawn_white.png",
-        ),
-        (
-            PieceType::Rook,
-            game::Color::White,
-            "./assets/rook_white.png",
-        ),
-        (
-            PieceType::Bishop,
-            game::Color::White,
-            "./assets/bishop_white.png",
-        ),
-        (
-            PieceType::Knight,
-            game::Color::White,
-            "./assets/knight_white.png",
-        ),
-        (
-            PieceType::Queen,
-            game::Color::White,
-            "./assets/queen_white.png",
-        ),
-        (
-            PieceType::King,
-            game::Color::White,
-            "./assets/king_white.png",
-        ),
-    ];
-    for (piece_type, color, path) in pieces {
-        map.insert((piece_type, color), load_texture(path).await.unwrap());
+fn promotion_order(mv: Move) -> usize {
+    match mv {
+        Move::Promotion(mv) => PROMOTION_ORDER
+            .iter()
+            .position(|&piece_type| piece_type == mv.new_piece.piece_type)
+            .unwrap_or(PROMOTION_ORDER.len()),
+        _ => PROMOTION_ORDER.len() + 1,
     }
 }
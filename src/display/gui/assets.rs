@@ -0,0 +1,202 @@
+use crate::{game, piece::PieceType, variant::VARIANTS};
+use macroquad::prelude::*;
+use resvg::{tiny_skia, usvg};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+pub(super) type PieceTextures = HashMap<(PieceType, game::Color), Texture2D>;
+
+// Side of the square SVG pieces are drawn into, sharp enough for a full screen board
+const SVG_TEXTURE_SIZE: u32 = 256;
+
+// The piece types a set has to have images for, named as in its files. Images of the
+// variants' fairy pieces are optional; those without one are drawn as a lettered disc.
+const PIECE_FILE_NAMES: [(PieceType, &str); 6] = [
+    (PieceType::Pawn, "pawn"),
+    (PieceType::Rook, "rook"),
+    (PieceType::Bishop, "bishop"),
+    (PieceType::Knight, "knight"),
+    (PieceType::Queen, "queen"),
+    (PieceType::King, "king"),
+];
+
+// The default assets are built into the binary, so it runs from any directory
+const DEFAULT_BACKGROUND: &[u8] = include_bytes!("../../../assets/background.png");
+const DEFAULT_PIECES: [(PieceType, game::Color, &[u8]); 12] = [
+    (
+        PieceType::Pawn,
+        game::Color::Black,
+        include_bytes!("../../../assets/pawn_black.png"),
+    ),
+    (
+        PieceType::Rook,
+        game::Color::Black,
+        include_bytes!("../../../assets/rook_black.png"),
+    ),
+    (
+        PieceType::Bishop,
+        game::Color::Black,
+        include_bytes!("../../../assets/bishop_black.png"),
+    ),
+    (
+        PieceType::Knight,
+        game::Color::Black,
+        include_bytes!("../../../assets/knight_black.png"),
+    ),
+    (
+        PieceType::Queen,
+        game::Color::Black,
+        include_bytes!("../../../assets/queen_black.png"),
+    ),
+    (
+        PieceType::King,
+        game::Color::Black,
+        include_bytes!("../../../assets/king_black.png"),
+    ),
+    (
+        PieceType::Pawn,
+        game::Color::White,
+        include_bytes!("../../../assets/pawn_white.png"),
+    ),
+    (
+        PieceType::Rook,
+        game::Color::White,
+        include_bytes!("../../../assets/rook_white.png"),
+    ),
+    (
+        PieceType::Bishop,
+        game::Color::White,
+        include_bytes!("../../../assets/bishop_white.png"),
+    ),
+    (
+        PieceType::Knight,
+        game::Color::White,
+        include_bytes!("../../../assets/knight_white.png"),
+    ),
+    (
+        PieceType::Queen,
+        game::Color::White,
+        include_bytes!("../../../assets/queen_white.png"),
+    ),
+    (
+        PieceType::King,
+        game::Color::White,
+        include_bytes!("../../../assets/king_white.png"),
+    ),
+];
+
+pub(super) fn default_background() -> Texture2D {
+    Texture2D::from_file_with_format(DEFAULT_BACKGROUND, Some(ImageFormat::Png))
+}
+
+pub(super) fn default_pieces() -> PieceTextures {
+    DEFAULT_PIECES
+        .iter()
+        .map(|&(piece_type, color, bytes)| {
+            let texture = Texture2D::from_file_with_format(bytes, Some(ImageFormat::Png));
+            ((piece_type, color), texture)
+        })
+        .collect()
+}
+
+// Reads the piece set in `dir`, one PNG or SVG file per piece named like `knight_white.png`
+pub(super) fn load_piece_set(dir: &Path) -> Result<PieceTextures, io::Error> {
+    let mut textures = HashMap::new();
+    let mut missing = Vec::new();
+    let standard = PIECE_FILE_NAMES
+        .iter()
+        .map(|&(piece_type, name)| (piece_type, name.to_string(), true));
+    let fairy = VARIANTS
+        .iter()
+        .flat_map(|variant| variant.fairy_pieces())
+        .map(|definition| {
+            (
+                definition.piece_type(),
+                definition.name.to_lowercase(),
+                false,
+            )
+        });
+    for (piece_type, name, required) in standard.chain(fairy) {
+        // Capablanca and Grand chess share their fairy pieces
+        if textures.contains_key(&(piece_type, game::Color::White)) {
+            continue;
+        }
+        for (color, color_name) in [(game::Color::White, "white"), (game::Color::Black, "black")] {
+            let stem = format!("{}_{}", name, color_name);
+            match load_piece_image(dir, &stem)? {
+                Some(texture) => {
+                    textures.insert((piece_type, color), texture);
+                }
+                None if required => missing.push(stem),
+                None => {}
+            }
+        }
+    }
+    if !missing.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "no PNG or SVG image in {} for {}",
+                dir.display(),
+                missing.join(", ")
+            ),
+        ));
+    }
+    Ok(textures)
+}
+
+// The piece's PNG if there is one, otherwise its SVG
+fn load_piece_image(dir: &Path, stem: &str) -> Result<Option<Texture2D>, io::Error> {
+    let png = dir.join(format!("{}.png", stem));
+    if png.is_file() {
+        let image = Image::from_file_with_format(&fs::read(&png)?, Some(ImageFormat::Png))
+            .map_err(|err| invalid_image(&png, err))?;
+        return Ok(Some(Texture2D::from_image(&image)));
+    }
+    let svg = dir.join(format!("{}.svg", stem));
+    if svg.is_file() {
+        return svg_texture(&fs::read(&svg)?)
+            .map(Some)
+            .map_err(|err| invalid_image(&svg, err));
+    }
+    Ok(None)
+}
+
+// Draws the SVG into a square texture, scaled to fit and centred
+fn svg_texture(data: &[u8]) -> Result<Texture2D, String> {
+    let tree =
+        usvg::Tree::from_data(data, &usvg::Options::default()).map_err(|err| err.to_string())?;
+    let mut pixmap =
+        tiny_skia::Pixmap::new(SVG_TEXTURE_SIZE, SVG_TEXTURE_SIZE).ok_or("texture size is zero")?;
+    let size = tree.size();
+    let scale = SVG_TEXTURE_SIZE as f32 / size.width().max(size.height());
+    let transform = tiny_skia::Transform::from_scale(scale, scale).post_translate(
+        (SVG_TEXTURE_SIZE as f32 - size.width() * scale) / 2.,
+        (SVG_TEXTURE_SIZE as f32 - size.height() * scale) / 2.,
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // tiny-skia keeps colours premultiplied by alpha, textures don't
+    let bytes: Vec<u8> = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    Ok(Texture2D::from_rgba8(
+        SVG_TEXTURE_SIZE as u16,
+        SVG_TEXTURE_SIZE as u16,
+        &bytes,
+    ))
+}
+
+fn invalid_image(path: &Path, err: impl ToString) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("could not read {}: {}", path.display(), err.to_string()),
+    )
+}
@@ -0,0 +1,368 @@
+use super::{GUIDisplayer, T_GOLD, T_GREY, T_ORANGE};
+use crate::{
+    UserAction,
+    game::{self, Game, board_position_to_notation},
+    piece::{Piece, PieceDefinition, PieceType},
+    validate::FenValidation,
+};
+use macroquad::miniquad::window::{clipboard_get, clipboard_set};
+use macroquad::prelude::*;
+
+// Pieces to place, followed by the variant's fairy pieces, as far down the board as
+// there is room for them
+const PALETTE_PIECES: [PieceType; 6] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+const HELP: [&str; 3] = [
+    "Click: place  Right click: remove  Shift+click: en passant square",
+    "Tab: side to move  K/Q: castling, Shift for Black  Del: clear  Home: start position",
+    "Enter: play  A: analyse  F: type FEN  Ctrl+C/V: copy/paste FEN  Esc: back",
+];
+
+// A position being set up, kept as the fields of its FEN until it is played
+pub(super) struct BoardEditor {
+    // The board, its size and variant, and the side to move
+    position: Game,
+    castling: String,
+    en_passant: Option<(usize, usize)>,
+    palette_piece: Piece,
+    // The text typed so far while the FEN field is open
+    fen_input: Option<String>,
+    // Why the position can't be played, or what became of the last FEN import
+    problems: Vec<String>,
+}
+
+impl BoardEditor {
+    pub(super) fn new(game: &Game) -> Self {
+        let mut editor = BoardEditor {
+            position: Game::from_blank_board_sized(game.rows, game.cols),
+            castling: String::new(),
+            en_passant: None,
+            palette_piece: Piece {
+                color: game::Color::White,
+                piece_type: PieceType::King,
+                has_moved: false,
+                promoted: false,
+            },
+            fen_input: None,
+            problems: Vec::new(),
+        };
+        editor.position.set_variant(game.variant);
+        editor.load_fen(&game.to_full_fen_str());
+        editor
+    }
+
+    pub(super) fn size(&self) -> (usize, usize) {
+        (self.position.rows, self.position.cols)
+    }
+
+    pub(super) fn fen(&self) -> String {
+        let side = match self.position.next_player {
+            game::Color::White => 'w',
+            game::Color::Black => 'b',
+        };
+        let castling = if self.castling.is_empty() {
+            "-"
+        } else {
+            &self.castling
+        };
+        let en_passant = self.en_passant.map_or("-".to_string(), |(row, col)| {
+            board_position_to_notation(row, col)
+        });
+        format!(
+            "{} {} {} {} 0 1",
+            self.position.to_fen_str(),
+            side,
+            castling,
+            en_passant
+        )
+    }
+
+    // Take the board and the other fields from a FEN, keeping the variant
+    fn load_fen(&mut self, fen: &str) {
+        let validation = FenValidation::Lenient;
+        let position = match Game::from_fen_str_validated(fen, self.position.variant, validation) {
+            Ok((position, _)) => position,
+            Err(err) => {
+                self.problems = vec![format!("Could not read FEN: {}", err)];
+                return;
+            }
+        };
+        // As written, so rights and squares the position can't use show up as problems
+        let mut fields = fen.split_whitespace().skip(2);
+        self.castling = match fields.next() {
+            Some("-") | None => String::new(),
+            Some(castling) => castling.to_string(),
+        };
+        self.en_passant = fields
+            .next()
+            .and_then(|square| position.notation_to_board_position(square));
+        self.position = position;
+        self.update_problems();
+    }
+
+    fn update_problems(&mut self) {
+        let validation = FenValidation::Lenient;
+        self.problems =
+            match Game::from_fen_str_validated(&self.fen(), self.position.variant, validation) {
+                Ok((_, errors)) => errors.iter().map(ToString::to_string).collect(),
+                Err(err) => vec![err.to_string()],
+            };
+    }
+
+    fn toggle_castling(&mut self, right: char) {
+        if self.castling.contains(right) {
+            self.castling.retain(|chr| chr != right);
+        } else {
+            self.castling.push(right);
+            // Back in the usual KQkq order
+            let order = |chr: char| "KQkq".find(chr).unwrap_or(4);
+            let mut rights: Vec<char> = self.castling.chars().collect();
+            rights.sort_by_key(|&chr| order(chr));
+            self.castling = rights.into_iter().collect();
+        }
+    }
+
+    fn palette(&self) -> impl Iterator<Item = (usize, Piece)> {
+        let rows = self.position.rows;
+        let fairy_pieces = self.position.variant.fairy_pieces();
+        [game::Color::White, game::Color::Black]
+            .into_iter()
+            .enumerate()
+            .flat_map(move |(column, color)| {
+                PALETTE_PIECES
+                    .iter()
+                    .copied()
+                    .chain(fairy_pieces.iter().map(PieceDefinition::piece_type))
+                    .take(rows)
+                    .enumerate()
+                    .map(move |(index, piece_type)| {
+                        let piece = Piece {
+                            color,
+                            piece_type,
+                            has_moved: false,
+                            promoted: false,
+                        };
+                        (column * rows + index, piece)
+                    })
+            })
+    }
+}
+
+impl GUIDisplayer {
+    pub(super) fn open_editor(&mut self, game: &Game) {
+        let editor = BoardEditor::new(game);
+        (self.rows, self.cols) = editor.size();
+        self.editor = Some(editor);
+        self.selected_piece_square = None;
+        self.selected_drop = None;
+        self.recalculate();
+    }
+
+    fn close_editor(&mut self) {
+        self.editor = None;
+        self.recalculate();
+    }
+
+    // The palette fills the two columns right of the board, White's pieces then Black's
+    fn palette_square_pixels(&self, slot: usize) -> (f32, f32) {
+        let (column, index) = (slot / self.rows, slot % self.rows);
+        self.layout_square_pixels(index, self.cols + column)
+    }
+
+    fn palette_piece_under_mouse(&self) -> Option<Piece> {
+        let editor = self.editor.as_ref()?;
+        let (mx, my) = mouse_position();
+        editor.palette().find_map(|(slot, piece)| {
+            let (x, y) = self.palette_square_pixels(slot);
+            ((x <= mx && mx < x + self.square_size) && (y <= my && my < y + self.square_size))
+                .then_some(piece)
+        })
+    }
+
+    pub(super) fn editor_input(&mut self) -> Option<UserAction> {
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        let control = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let palette_piece = self.palette_piece_under_mouse();
+        let square = self.get_mouse_position_pair();
+        let editor = self.editor.as_mut()?;
+
+        if let Some(text) = editor.fen_input.as_mut() {
+            while let Some(chr) = get_char_pressed() {
+                if !chr.is_control() && !control {
+                    text.push(chr);
+                }
+            }
+            if is_key_pressed(KeyCode::Backspace) {
+                text.pop();
+            }
+            if control && is_key_pressed(KeyCode::V) {
+                text.push_str(clipboard_get().unwrap_or_default().trim());
+            }
+            if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+                let fen = editor.fen_input.take().unwrap_or_default();
+                editor.load_fen(&fen);
+                (self.rows, self.cols) = editor.size();
+                self.recalculate();
+            } else if is_key_pressed(KeyCode::Escape) {
+                editor.fen_input = None;
+            }
+            return None;
+        }
+        // Typed characters only matter to the FEN field, so don't let them pile up
+        while get_char_pressed().is_some() {}
+
+        if is_mouse_button_pressed(MouseButton::Left) {
+            if let Some(piece) = palette_piece {
+                editor.palette_piece = piece;
+            } else if let Some(square) = square {
+                if shift {
+                    editor.en_passant = (editor.en_passant != Some(square)).then_some(square);
+                } else {
+                    editor.position.board[square.0][square.1] = Some(editor.palette_piece);
+                }
+                editor.update_problems();
+            }
+        }
+        if is_mouse_button_pressed(MouseButton::Right)
+            && let Some((i, j)) = square
+        {
+            editor.position.board[i][j] = None;
+            editor.update_problems();
+        }
+
+        if is_key_pressed(KeyCode::Tab) {
+            editor.position.next_player = editor.position.next_player.opposite();
+            editor.update_problems();
+        }
+        for (key, right) in [(KeyCode::K, 'K'), (KeyCode::Q, 'Q')] {
+            if is_key_pressed(key) {
+                editor.toggle_castling(if shift {
+                    right.to_ascii_lowercase()
+                } else {
+                    right
+                });
+                editor.update_problems();
+            }
+        }
+        if is_key_pressed(KeyCode::Delete) {
+            editor.position.clear();
+            editor.en_passant = None;
+            editor.update_problems();
+        }
+        if is_key_pressed(KeyCode::Home) {
+            let start_fen = editor.position.variant.start_fen();
+            editor.load_fen(start_fen);
+        }
+
+        if control && is_key_pressed(KeyCode::C) {
+            clipboard_set(&editor.fen());
+        }
+        if control && is_key_pressed(KeyCode::V) {
+            match clipboard_get() {
+                Some(fen) => editor.load_fen(fen.trim()),
+                None => editor.problems = vec!["The clipboard is empty".to_string()],
+            }
+        }
+        if is_key_pressed(KeyCode::F) {
+            editor.fen_input = Some(String::new());
+        }
+
+        let analyse = !control && is_key_pressed(KeyCode::A);
+        if (is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) || analyse)
+            && editor.problems.is_empty()
+        {
+            let fen = editor.fen();
+            self.analysis = analyse;
+            self.close_editor();
+            return Some(UserAction::SetUpPosition(fen));
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.close_editor();
+            return None;
+        }
+
+        // Loading a FEN can change the board size
+        if let Some(editor) = &self.editor
+            && editor.size() != (self.rows, self.cols)
+        {
+            (self.rows, self.cols) = editor.size();
+            self.recalculate();
+        }
+        None
+    }
+
+    pub(super) fn draw_editor(&self) {
+        let Some(editor) = &self.editor else {
+            return;
+        };
+
+        self.draw_board();
+        self.draw_board_pieces(&editor.position);
+        if let Some((i, j)) = editor.en_passant {
+            self.highlight_square(i, j, T_GOLD);
+        }
+
+        for (slot, piece) in editor.palette() {
+            let (x, y) = self.palette_square_pixels(slot);
+            let background = if piece == editor.palette_piece {
+                T_ORANGE
+            } else {
+                T_GREY
+            };
+            draw_rectangle(x, y, self.square_size, self.square_size, background);
+            self.draw_piece(&piece, x, y);
+        }
+
+        let castling = if editor.castling.is_empty() {
+            "no castling".to_string()
+        } else {
+            format!("castling {}", editor.castling)
+        };
+        let en_passant = editor
+            .en_passant
+            .map_or("no en passant".to_string(), |(i, j)| {
+                format!("en passant {}", board_position_to_notation(i, j))
+            });
+        let mut lines = vec![format!(
+            "{} to move, {}, {}",
+            editor.position.next_player, castling, en_passant
+        )];
+        lines.extend(HELP.iter().map(|line| line.to_string()));
+        match &editor.fen_input {
+            Some(text) => lines.push(format!("FEN (Enter to load): {}_", text)),
+            None => lines.push(editor.fen()),
+        }
+        lines.extend(editor.problems.iter().cloned());
+        self.draw_text_panel(&lines);
+    }
+
+    // Lines of text in a box along the bottom of the window
+    fn draw_text_panel(&self, lines: &[String]) {
+        let font_size = self.square_size / 5.;
+        let line_height = font_size * 1.2;
+        let padding = font_size / 4.;
+        let width = lines
+            .iter()
+            .map(|line| measure_text(line, None, font_size as u16, 1.).width)
+            .fold(0., f32::max);
+        let height = line_height * lines.len() as f32 + padding * 2.;
+        let y = self.sh - height;
+        draw_rectangle(0., y, width + padding * 2., height, T_GREY);
+        for (index, line) in lines.iter().enumerate() {
+            draw_text(
+                line,
+                padding,
+                y + padding + line_height * (index as f32 + 0.8),
+                font_size,
+                WHITE,
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use super::{GUIDisplayer, promotion_order};
+use crate::{
+    UserAction,
+    game::{self, Game, GameState},
+    moves::Move,
+    piece::PieceType,
+};
+use macroquad::prelude::*;
+
+// Circles the check glow is drawn with, fading out towards the edge of the square
+const CHECK_GLOW_STEPS: usize = 6;
+
+impl GUIDisplayer {
+    // Squares the piece on `from` can go to, and whether it captures there. The player's
+    // pieces can also be picked up while the opponent is to move, to queue a premove.
+    pub(super) fn selection_targets(
+        &self,
+        game: &Game,
+        from: (usize, usize),
+    ) -> Vec<((usize, usize), bool)> {
+        let Some(piece) = game.board[from.0][from.1] else {
+            return Vec::new();
+        };
+        if piece.color == game.next_player {
+            game.moves_from_square(from)
+                .iter()
+                .map(|mv| (mv.get_to_position(), mv.is_capture()))
+                .collect()
+        } else if piece.color == self.player_color {
+            premove_targets(game, from)
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Queue the selected piece's move to play as soon as the opponent has moved
+    pub(super) fn set_premove(&mut self, game: &Game, to: (usize, usize)) {
+        let Some(from) = self.selected_piece_square else {
+            return;
+        };
+        if self
+            .selection_targets(game, from)
+            .iter()
+            .any(|&(target, _)| target == to)
+        {
+            self.premove = Some((from, to));
+            self.selected_piece_square = None;
+        }
+    }
+
+    // The queued move once it is the player's turn, promoting to the first choice offered.
+    // It is dropped if the opponent's move made it illegal.
+    pub(super) fn play_premove(&mut self, game: &Game) -> Option<UserAction> {
+        if game.next_player != self.player_color || self.history_view.is_some() {
+            return None;
+        }
+        let (from, to) = self.premove.take()?;
+        let mut moves: Vec<Move> = game
+            .moves_from_square(from)
+            .into_iter()
+            .filter(|mv| mv.get_to_position() == to)
+            .collect();
+        moves.sort_by_key(|mv| promotion_order(*mv));
+        match moves.first() {
+            Some(mv) => Some(UserAction::Move(*mv)),
+            None => {
+                self.add_message("Premove is no longer legal".to_string());
+                None
+            }
+        }
+    }
+
+    pub(super) fn draw_last_move(&self, game: &Game) {
+        if let Some(mv) = game.move_history.last() {
+            for (i, j) in [mv.get_from_position(), mv.get_to_position()] {
+                self.highlight_square(i, j, self.theme.last_move);
+            }
+        }
+    }
+
+    pub(super) fn draw_premove(&self) {
+        if let Some((from, to)) = self.premove {
+            for (i, j) in [from, to] {
+                self.highlight_square(i, j, self.theme.premove);
+            }
+        }
+    }
+
+    // A red glow under the king of the side in check, or the one mated
+    pub(super) fn draw_check(&self, game: &Game) {
+        let color = match game.state {
+            GameState::InCheck(color) | GameState::Checkmate(color) => color,
+            _ => return,
+        };
+        let step_alpha = self.theme.check.a / CHECK_GLOW_STEPS as f32;
+        for i in 0..game.rows {
+            for j in 0..game.cols {
+                if !game.board[i][j]
+                    .is_some_and(|p| p.piece_type == PieceType::King && p.color == color)
+                {
+                    continue;
+                }
+                let (x, y) = self.board_square_pixels(i, j);
+                let (centre_x, centre_y) = (x + self.square_size / 2., y + self.square_size / 2.);
+                for step in 0..CHECK_GLOW_STEPS {
+                    let radius =
+                        self.square_size / 2. * (1. - step as f32 / CHECK_GLOW_STEPS as f32);
+                    draw_circle(
+                        centre_x,
+                        centre_y,
+                        radius,
+                        Color {
+                            a: step_alpha,
+                            ..self.theme.check
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    // A dot on a square the piece moves to, or a ring round a piece it captures
+    pub(super) fn draw_move_target(&self, (i, j): (usize, usize), capture: bool) {
+        let (x, y) = self.board_square_pixels(i, j);
+        let (centre_x, centre_y) = (x + self.square_size / 2., y + self.square_size / 2.);
+        if capture {
+            let thickness = self.square_size / 12.;
+            draw_circle_lines(
+                centre_x,
+                centre_y,
+                (self.square_size - thickness) / 2.,
+                thickness,
+                self.theme.capture_ring,
+            );
+        } else {
+            draw_circle(
+                centre_x,
+                centre_y,
+                self.square_size / 6.,
+                self.theme.move_dot,
+            );
+        }
+    }
+}
+
+// Where the piece could go if the opponent's move allows it: its moves as the board stands,
+// and for a pawn the squares it captures on even while they are empty
+fn premove_targets(game: &Game, from: (usize, usize)) -> Vec<((usize, usize), bool)> {
+    let Some(piece) = game.board[from.0][from.1] else {
+        return Vec::new();
+    };
+    let mut targets: Vec<((usize, usize), bool)> = game
+        .get_piece_moves(from)
+        .iter()
+        .map(|mv| (mv.get_to_position(), mv.is_capture()))
+        .collect();
+    if piece.piece_type == PieceType::Pawn {
+        let row = match piece.color {
+            game::Color::White => from.0 + 1,
+            game::Color::Black => from.0.wrapping_sub(1),
+        };
+        for col in [from.1.wrapping_sub(1), from.1 + 1] {
+            if game.valid_position((row, col)) && game.board[row][col].is_none() {
+                targets.push(((row, col), false));
+            }
+        }
+    }
+    targets
+}
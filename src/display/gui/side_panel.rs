@@ -0,0 +1,211 @@
+use super::{GUIDisplayer, T_GREY, T_ORANGE};
+use crate::game::{self, Game};
+use crate::variant::is_standard;
+use macroquad::prelude::*;
+
+// Share of the window width taken by the panel left of the board
+pub(super) const SIDE_PANEL_WIDTH: f32 = 0.22;
+// Lines at the top of the panel for the variant, the opening and the latest messages
+const MAX_MESSAGES: usize = 4;
+const HEADER_LINES: usize = 2 + MAX_MESSAGES;
+const PANEL_MAX_LINES: usize = 30;
+
+// A past position of the game on show instead of the current one
+pub(super) struct HistoryView {
+    // Moves played to reach it
+    pub(super) ply: usize,
+    // The game it was taken from, which it stops matching once a move is made or taken back
+    game_key: (u64, usize),
+    pub(super) position: Game,
+}
+
+impl GUIDisplayer {
+    pub(super) fn add_message(&mut self, message: String) {
+        if self.messages.last() == Some(&message) {
+            return;
+        }
+        self.messages.push(message);
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+    }
+
+    // Show the position after `ply` moves, or the current one
+    fn view_ply(&mut self, game: &Game, ply: usize) {
+        let moves = game.move_history.len();
+        if ply >= moves {
+            self.history_view = None;
+            return;
+        }
+        let mut position = game.clone();
+        for _ in ply..moves {
+            position.undo_move();
+        }
+        self.history_view = Some(HistoryView {
+            ply,
+            game_key: (game.position_hash(), moves),
+            position,
+        });
+        self.selected_piece_square = None;
+        self.selected_drop = None;
+        self.promotion_choices = None;
+    }
+
+    // Back to the current position when the game has moved on from the one viewed
+    pub(super) fn sync_history_view(&mut self, game: &Game) {
+        if self
+            .history_view
+            .as_ref()
+            .is_some_and(|view| view.game_key != (game.position_hash(), game.move_history.len()))
+        {
+            self.history_view = None;
+        }
+    }
+
+    fn shown_ply(&self, game: &Game) -> usize {
+        self.history_view
+            .as_ref()
+            .map_or(game.move_history.len(), |view| view.ply)
+    }
+
+    fn update_move_list(&mut self, game: &Game) {
+        let key = (game.position_hash(), game.move_history.len());
+        if self
+            .move_list
+            .as_ref()
+            .is_some_and(|(cached, _)| *cached == key)
+        {
+            return;
+        }
+        self.move_list = Some((key, game.to_pgn_game().main_line()));
+    }
+
+    // Left and right arrows step through the game, Home and End jump to its ends
+    pub(super) fn navigation_input(&mut self, game: &Game) {
+        let ply = self.shown_ply(game);
+        let moves = game.move_history.len();
+        if is_key_pressed(KeyCode::Left) {
+            self.view_ply(game, ply.saturating_sub(1));
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.view_ply(game, ply + 1);
+        }
+        if is_key_pressed(KeyCode::Home) {
+            self.view_ply(game, 0);
+        }
+        if is_key_pressed(KeyCode::End) {
+            self.view_ply(game, moves);
+        }
+        if is_mouse_button_pressed(MouseButton::Left)
+            && let Some(index) = self.move_under_mouse(game)
+        {
+            self.view_ply(game, index + 1);
+        }
+    }
+
+    // Width, line height and top of the move list
+    fn side_panel_layout(&self) -> (f32, f32, f32) {
+        let width = self.sw * SIDE_PANEL_WIDTH;
+        let line_height = self.sh / PANEL_MAX_LINES as f32;
+        (width, line_height, line_height * (HEADER_LINES + 1) as f32)
+    }
+
+    // Black's first move goes in the second column if Black moved first
+    fn black_started(&self, game: &Game) -> bool {
+        (game.next_player == game::Color::Black) == game.move_history.len().is_multiple_of(2)
+    }
+
+    // The first row of moves shown, keeping the one on the board in sight
+    fn first_move_row(&self, game: &Game) -> usize {
+        let (_, line_height, top) = self.side_panel_layout();
+        let visible_rows = (((self.sh - top) / line_height) as usize).max(1);
+        let offset = self.black_started(game) as usize;
+        let shown_row = (self.shown_ply(game) + offset).saturating_sub(1) / 2;
+        (shown_row + 1).saturating_sub(visible_rows)
+    }
+
+    // Index in the game of the move clicked in the list
+    fn move_under_mouse(&self, game: &Game) -> Option<usize> {
+        if self.editor.is_some() {
+            return None;
+        }
+        let (width, line_height, top) = self.side_panel_layout();
+        let (mx, my) = mouse_position();
+        if mx >= width || my < top {
+            return None;
+        }
+        let row = ((my - top) / line_height) as usize + self.first_move_row(game);
+        let column = if mx < width * 0.6 { 0 } else { 1 };
+        let index = (row * 2 + column).checked_sub(self.black_started(game) as usize)?;
+        (index < game.move_history.len()).then_some(index)
+    }
+
+    // Variant, opening and messages above the game's moves, the one on the board marked
+    pub(super) fn draw_side_panel(&mut self, game: &Game, shown: &Game) {
+        self.update_move_list(game);
+        let (width, line_height, top) = self.side_panel_layout();
+        let font_size = line_height * 0.8;
+        let padding = font_size / 4.;
+        draw_rectangle(0., 0., width, self.sh, T_GREY);
+
+        let mut header = Vec::new();
+        if !is_standard(shown.variant) {
+            let mut text = shown.variant.name().to_string();
+            if let (Some(white), Some(black)) = (
+                shown.checks_remaining(game::Color::White),
+                shown.checks_remaining(game::Color::Black),
+            ) {
+                text.push_str(&format!(", checks left: {}-{}", white, black));
+            }
+            header.push(text);
+        }
+        if let Some(opening) = self.opening_name(shown) {
+            header.push(opening);
+        }
+        header.extend(self.messages.iter().cloned());
+        for (line, text) in header.iter().take(HEADER_LINES).enumerate() {
+            draw_text(
+                text,
+                padding,
+                line_height * (line as f32 + 0.8),
+                font_size,
+                WHITE,
+            );
+        }
+
+        let Some((_, sans)) = &self.move_list else {
+            return;
+        };
+        let offset = self.black_started(game) as usize;
+        let first_row = self.first_move_row(game);
+        let shown_index = self.shown_ply(game).checked_sub(1);
+        for (index, san) in sans.iter().enumerate() {
+            let (row, column) = ((index + offset) / 2, (index + offset) % 2);
+            let Some(line) = row.checked_sub(first_row) else {
+                continue;
+            };
+            let y = top + line_height * line as f32;
+            if y >= self.sh {
+                break;
+            }
+            let x = if column == 0 {
+                width * 0.25
+            } else {
+                width * 0.6
+            };
+            if shown_index == Some(index) {
+                draw_rectangle(x - padding, y, width * 0.35, line_height, T_ORANGE);
+            }
+            if column == 0 || index == 0 {
+                draw_text(
+                    &format!("{}.", row + 1),
+                    padding,
+                    y + line_height * 0.8,
+                    font_size,
+                    WHITE,
+                );
+            }
+            draw_text(san, x, y + line_height * 0.8, font_size, WHITE);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use super::{T_GREY, T_ORANGE};
+use macroquad::prelude::*;
+
+// The board's light and dark square colours
+#[derive(Copy, Clone, Debug)]
+pub struct BoardScheme {
+    pub name: &'static str,
+    pub light: Color,
+    pub dark: Color,
+}
+
+// The schemes T cycles through in the GUI, the first one by default
+pub const BOARD_SCHEMES: [BoardScheme; 4] = [
+    BoardScheme {
+        name: "green",
+        light: WHITE,
+        dark: Color {
+            r: 119. / 255.,
+            g: 149. / 255.,
+            b: 86. / 255.,
+            a: 1.,
+        },
+    },
+    BoardScheme {
+        name: "brown",
+        light: Color {
+            r: 240. / 255.,
+            g: 217. / 255.,
+            b: 181. / 255.,
+            a: 1.,
+        },
+        dark: Color {
+            r: 181. / 255.,
+            g: 136. / 255.,
+            b: 99. / 255.,
+            a: 1.,
+        },
+    },
+    BoardScheme {
+        name: "blue",
+        light: Color {
+            r: 222. / 255.,
+            g: 227. / 255.,
+            b: 230. / 255.,
+            a: 1.,
+        },
+        dark: Color {
+            r: 140. / 255.,
+            g: 162. / 255.,
+            b: 173. / 255.,
+            a: 1.,
+        },
+    },
+    BoardScheme {
+        name: "grey",
+        light: Color {
+            r: 0.88,
+            g: 0.88,
+            b: 0.88,
+            a: 1.,
+        },
+        dark: Color {
+            r: 0.55,
+            g: 0.55,
+            b: 0.55,
+            a: 1.,
+        },
+    },
+];
+
+pub fn board_scheme(name: &str) -> Option<BoardScheme> {
+    BOARD_SCHEMES
+        .iter()
+        .find(|scheme| scheme.name.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+// Colours the GUI draws the board and marks squares with
+#[derive(Copy, Clone, Debug)]
+pub struct Theme {
+    pub board: BoardScheme,
+    // The selected piece's square
+    pub selected: Color,
+    // Where the last move went from and to
+    pub last_move: Color,
+    // The glow around a king in check
+    pub check: Color,
+    // Dots on the empty squares the selected piece can move to
+    pub move_dot: Color,
+    // Rings on the squares where it captures
+    pub capture_ring: Color,
+    // The squares of a move queued while the opponent is thinking
+    pub premove: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            board: BOARD_SCHEMES[0],
+            selected: T_ORANGE,
+            last_move: Color {
+                r: 0.61,
+                g: 0.78,
+                b: 0.1,
+                a: 0.45,
+            },
+            check: Color {
+                r: 1.,
+                g: 0.,
+                b: 0.,
+                a: 0.9,
+            },
+            move_dot: T_GREY,
+            capture_ring: Color {
+                r: 0.3,
+                g: 0.3,
+                b: 0.3,
+                a: 0.6,
+            },
+            premove: Color {
+                r: 0.55,
+                g: 0.25,
+                b: 0.8,
+                a: 0.45,
+            },
+        }
+    }
+}
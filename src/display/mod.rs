@@ -1,10 +1,30 @@
-use crate::game::Game;
+use crate::game::{Color, Game};
 use crate::moves::Move;
 pub mod cli;
 pub mod gui;
 
+#[derive(Clone, Debug, PartialEq, Hash, Eq)]
+pub enum UserAction {
+    Move(Move),
+    Undo,
+    Redo,
+    SavePgn(String),
+    // Start over from a random Fischer random start position
+    NewChess960Game,
+    // Start over with the named variant
+    NewGame(String),
+    // Start over from the position in the FEN, keeping the variant
+    SetUpPosition(String),
+    // Stop playing and close the game
+    Quit,
+}
+
 pub trait Display {
     async fn display(&mut self, game: &Game);
-    fn user_input(&mut self, game: &Game) -> Option<Move>;
-    fn display_message(&self, message: String);
+    fn user_input(&mut self, game: &Game) -> Option<UserAction>;
+    fn display_message(&mut self, message: String);
+    // Ask the given player whether they accept their opponent's takeback request
+    async fn confirm_takeback(&mut self, game: &Game, color: Color) -> bool;
+    // The side the person at this display plays, so the board is shown from their side
+    fn set_player_color(&mut self, color: Color);
 }
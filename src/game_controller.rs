@@ -1,9 +1,13 @@
-use crate::Display;
-use crate::game::{Game, GameState};
+use crate::game::{Color, Game, GameState, random_chess960_index};
+use crate::validate::FenValidation;
+use crate::variant::variant_from_name;
+use crate::{Display, UserAction};
+use std::fs;
 
 pub struct GameController<D: Display> {
     game: Game,
     displayer: D,
+    opponent_is_local: bool,
 }
 
 impl<D: Display> GameController<D> {
@@ -11,32 +15,248 @@ impl<D: Display> GameController<D> {
         GameController {
             game,
             displayer: display,
+            opponent_is_local: true,
         }
     }
 
+    // When the opponent isn't sitting at the same display, takebacks need their consent
+    pub fn set_opponent_local(&mut self, opponent_is_local: bool) {
+        self.opponent_is_local = opponent_is_local;
+    }
+
+    pub fn set_player_color(&mut self, color: Color) {
+        self.displayer.set_player_color(color);
+    }
+
     pub async fn run(&mut self) {
+        // The state last announced and after how many moves, so each is said only once.
+        // The game carries on after it ends so moves can still be taken back or a new one
+        // started.
+        let mut announced = None;
         loop {
-            match self.game.state {
-                GameState::Checkmate(col) => {
-                    self.displayer.display_message(format!("{} loses!", col));
-                    break;
+            let state = (self.game.state, self.game.move_history.len());
+            if announced != Some(state) {
+                announced = Some(state);
+                match self.game.state {
+                    GameState::Checkmate(col) => {
+                        self.displayer.display_message(format!("{} loses!", col));
+                    }
+                    GameState::Stalemate => {
+                        self.displayer.display_message("Stalemate!".to_string());
+                    }
+                    GameState::VariantWin(col) => {
+                        self.displayer.display_message(format!("{} wins!", col));
+                    }
+                    GameState::VariantDraw => {
+                        self.displayer.display_message("Draw!".to_string());
+                    }
+                    GameState::InCheck(col) => {
+                        self.displayer.display_message(format!("{} in check!", col));
+                    }
+                    _ => {}
                 }
-                GameState::Stalemate => {
-                    self.displayer.display_message("Stalemate!".to_string());
-                    break;
-                }
-                GameState::InCheck(col) => {
-                    self.displayer.display_message(format!("{} in check!", col));
-                }
-                _ => {}
             }
 
             self.displayer.display(&self.game).await;
 
-            let user_move = self.displayer.user_input(&self.game);
-            if let Some(mv) = user_move {
-                self.game.make_move(mv);
+            match self.displayer.user_input(&self.game) {
+                Some(UserAction::Move(mv)) => {
+                    self.game.make_move(mv);
+                }
+                Some(UserAction::Undo) => {
+                    self.request_takeback().await;
+                }
+                Some(UserAction::Redo) => {
+                    let redone = self.game.redo_move();
+                    if redone.is_none() {
+                        self.displayer
+                            .display_message("No move to redo!".to_string());
+                    }
+                }
+                Some(UserAction::SavePgn(path)) => match fs::write(&path, self.game.to_pgn()) {
+                    Ok(()) => self
+                        .displayer
+                        .display_message(format!("Saved game to {}", path)),
+                    Err(err) => self
+                        .displayer
+                        .display_message(format!("Could not save game to {}: {}", path, err)),
+                },
+                Some(UserAction::NewChess960Game) => {
+                    let index = random_chess960_index();
+                    self.game = Game::from_chess960_index(index).expect("Index is below 960");
+                    self.displayer
+                        .display_message(format!("New Chess960 game, start position {}", index));
+                }
+                Some(UserAction::NewGame(name)) => match variant_from_name(&name) {
+                    Some(variant) => {
+                        self.game = Game::from_variant(variant);
+                        self.displayer
+                            .display_message(format!("New {} game", variant.name()));
+                    }
+                    None => self
+                        .displayer
+                        .display_message(format!("Unknown variant {}", name)),
+                },
+                Some(UserAction::SetUpPosition(fen)) => self.set_up_position(&fen),
+                Some(UserAction::Quit) => break,
+                None => {}
             }
         }
     }
+
+    fn set_up_position(&mut self, fen: &str) {
+        match Game::from_fen_str_validated(fen, self.game.variant, FenValidation::Strict) {
+            Ok((game, _)) => self.game = game,
+            Err(err) => {
+                self.displayer
+                    .display_message(format!("Could not set up {}: {}", fen, err));
+                return;
+            }
+        }
+        self.displayer
+            .display_message("New game from the set up position".to_string());
+    }
+
+    async fn request_takeback(&mut self) {
+        if self.game.move_history.is_empty() {
+            self.displayer
+                .display_message("No move to take back!".to_string());
+            return;
+        }
+
+        // The player to move is the one who has to agree to the takeback
+        let opponent = self.game.next_player;
+        if !self.opponent_is_local && !self.displayer.confirm_takeback(&self.game, opponent).await {
+            self.displayer
+                .display_message(format!("{} declined the takeback", opponent));
+            return;
+        }
+        self.game.undo_move();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+    use std::collections::VecDeque;
+    use std::pin::pin;
+    use std::task::{Context, Waker};
+
+    // Plays back a fixed list of actions, then quits
+    struct ScriptedDisplay {
+        actions: VecDeque<UserAction>,
+        messages: Vec<String>,
+        // The answer to every takeback request
+        accepts_takeback: bool,
+    }
+
+    impl Display for ScriptedDisplay {
+        async fn display(&mut self, _game: &Game) {}
+
+        fn user_input(&mut self, _game: &Game) -> Option<UserAction> {
+            Some(self.actions.pop_front().unwrap_or(UserAction::Quit))
+        }
+
+        fn display_message(&mut self, message: String) {
+            self.messages.push(message);
+        }
+
+        async fn confirm_takeback(&mut self, _game: &Game, _color: Color) -> bool {
+            self.accepts_takeback
+        }
+
+        fn set_player_color(&mut self, _color: Color) {}
+    }
+
+    fn run_script(actions: Vec<UserAction>) -> GameController<ScriptedDisplay> {
+        run_remote_script(actions, None)
+    }
+
+    // With `takeback_answer` set, the opponent plays elsewhere and answers takebacks so
+    fn run_remote_script(
+        actions: Vec<UserAction>,
+        takeback_answer: Option<bool>,
+    ) -> GameController<ScriptedDisplay> {
+        let display = ScriptedDisplay {
+            actions: actions.into(),
+            messages: Vec::new(),
+            accepts_takeback: takeback_answer.unwrap_or(false),
+        };
+        let mut controller = GameController::new(Game::from_standard_board(), display);
+        controller.set_opponent_local(takeback_answer.is_none());
+        // Nothing in a scripted display waits, so the loop runs to the end in one poll
+        {
+            let run = pin!(controller.run());
+            let mut context = Context::from_waker(Waker::noop());
+            assert!(run.poll(&mut context).is_ready());
+        }
+        controller
+    }
+
+    fn moves(sans: &[&str]) -> Vec<UserAction> {
+        let mut game = Game::from_standard_board();
+        sans.iter()
+            .map(|san| {
+                let mv: Move = game.move_from_san(san).expect("Scripted move is legal");
+                game.make_move(mv);
+                UserAction::Move(mv)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn saves_the_result_after_the_game_ends() {
+        let path = std::env::temp_dir().join(format!("controller_test_{}.pgn", std::process::id()));
+        let mut actions = moves(&["f3", "e5", "g4", "Qh4#"]);
+        actions.push(UserAction::SavePgn(path.to_string_lossy().to_string()));
+        let controller = run_script(actions);
+
+        let pgn = fs::read_to_string(&path).expect("The finished game was saved");
+        fs::remove_file(&path).ok();
+        assert!(pgn.contains("[Result \"0-1\"]"));
+        assert!(pgn.trim_end().ends_with("Qh4# 0-1"));
+        assert_eq!(
+            controller.displayer.messages[..2],
+            [
+                "White loses!".to_string(),
+                format!("Saved game to {}", path.display())
+            ]
+        );
+    }
+
+    #[test]
+    fn takes_back_moves_after_the_game_ends_and_announces_each_state_once() {
+        let mut actions = moves(&["f3", "e5", "g4", "Qh4#"]);
+        actions.extend([UserAction::Undo, UserAction::Redo, UserAction::Undo]);
+        let controller = run_script(actions);
+
+        assert_eq!(controller.game.move_history.len(), 3);
+        assert_eq!(
+            controller.displayer.messages,
+            ["White loses!", "White loses!"]
+        );
+    }
+
+    #[test]
+    fn takeback_without_moves_says_so() {
+        let controller = run_script(vec![UserAction::Undo]);
+        assert_eq!(controller.displayer.messages, ["No move to take back!"]);
+    }
+
+    #[test]
+    fn remote_opponent_has_to_accept_a_takeback() {
+        let mut actions = moves(&["e4", "e5"]);
+        actions.push(UserAction::Undo);
+        let controller = run_remote_script(actions.clone(), Some(false));
+        assert_eq!(controller.game.move_history.len(), 2);
+        assert_eq!(
+            controller.displayer.messages,
+            ["White declined the takeback"]
+        );
+
+        let controller = run_remote_script(actions, Some(true));
+        assert_eq!(controller.game.move_history.len(), 1);
+        assert!(controller.displayer.messages.is_empty());
+    }
 }
@@ -1,8 +1,67 @@
-use chess::{game::Game, game_controller::GameController, gui::GUIDisplayer};
+use chess::{
+    game::{Color, Game},
+    game_controller::GameController,
+    game_database::GameDatabase,
+    gui::{BOARD_SCHEMES, GUIDisplayer, Theme, board_scheme},
+};
+use std::{env, path::Path, process};
+
+const USAGE: &str = "Usage: chess [--db DIR] [--black] [--pieces DIR] [--board NAME]";
 
 #[macroquad::main("Chess")]
 async fn main() {
-    let displayer = GUIDisplayer::new(8, 8).await;
+    let mut displayer = GUIDisplayer::new(8, 8);
+
+    // --db DIR opens a game database (see game_db) in the opening explorer, --black plays
+    // from Black's side, --pieces DIR loads a piece set and --board NAME sets the board colours
+    let mut play_black = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--black" => play_black = true,
+            "--db" => match args.next() {
+                Some(dir) => match GameDatabase::open_existing(&dir) {
+                    Ok(database) => displayer.set_explorer(database),
+                    Err(err) => eprintln!("Could not open game database {}: {}", dir, err),
+                },
+                None => eprintln!("--db needs a directory"),
+            },
+            "--pieces" => match args.next() {
+                Some(dir) => {
+                    if let Err(err) = displayer.set_piece_set(Path::new(&dir)) {
+                        eprintln!("Could not load piece set {}: {}", dir, err);
+                    }
+                }
+                None => eprintln!("--pieces needs a directory"),
+            },
+            "--board" => {
+                let name = args.next().unwrap_or_default();
+                match board_scheme(&name) {
+                    Some(board) => displayer.set_theme(Theme {
+                        board,
+                        ..Theme::default()
+                    }),
+                    None => eprintln!(
+                        "Unknown board colours {:?}, choose one of: {}",
+                        name,
+                        BOARD_SCHEMES
+                            .iter()
+                            .map(|scheme| scheme.name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }
+            }
+            _ => {
+                eprintln!("Unknown argument: {}\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
     let mut my_controller = GameController::new(Game::from_standard_board(), displayer);
+    if play_black {
+        my_controller.set_player_color(Color::Black);
+    }
     my_controller.run().await;
 }